@@ -0,0 +1,93 @@
+//! Producer/consumer throughput of a [Dump] for each bitmap width.
+//!
+//! A few threads throw into a shared dump while as many others recycle
+//! from it, until every thrown value has been recycled. Producers retry
+//! when the dump is full, which happens sooner the narrower it is.
+//! The size of each dump is reported along with its throughput.
+//!
+//! Run with `cargo bench --bench width`.
+
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use freelist::{BitmapInt, Dump};
+
+const PRODUCERS: usize = 2;
+const CONSUMERS: usize = 2;
+const THROWS_PER_PRODUCER: usize = 200_000;
+
+/// Runs the workload on a `Dump<u8, W, B>` and returns how long it took.
+fn run<const W: usize, B: BitmapInt + 'static>() -> Duration {
+    let dump = Arc::new(Dump::<u8, W, B>::new());
+    let recycled = Arc::new(AtomicUsize::new(0));
+    let total = PRODUCERS * THROWS_PER_PRODUCER;
+
+    let start = Instant::now();
+
+    let producers = (0..PRODUCERS).map(|producer| {
+        let dump = Arc::clone(&dump);
+
+        thread::spawn(move || {
+            // Values are never read, only told apart
+            for i in 0..THROWS_PER_PRODUCER {
+                let raw = ((producer * THROWS_PER_PRODUCER + i + 1) * 8) as *mut u8;
+
+                while dump.throw(raw).is_err() {
+                    thread::yield_now();
+                }
+            }
+        })
+    });
+
+    let consumers = (0..CONSUMERS).map(|_| {
+        let dump = Arc::clone(&dump);
+        let recycled = Arc::clone(&recycled);
+
+        thread::spawn(move || {
+            while recycled.load(Ordering::Relaxed) < total {
+                match dump.recycle() {
+                    Some(_) => {
+                        recycled.fetch_add(1, Ordering::Relaxed);
+                    }
+                    None => thread::yield_now(),
+                }
+            }
+        })
+    });
+
+    let handles: Vec<_> = producers.chain(consumers).collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    start.elapsed()
+}
+
+fn report<const W: usize, B: BitmapInt + 'static>() {
+    let elapsed = run::<W, B>();
+    let throws = (PRODUCERS * THROWS_PER_PRODUCER) as f64;
+
+    println!(
+        "width {:>2}: {:>7.2} M throws+recycles/s, {:>4} bytes per dump",
+        W,
+        throws / elapsed.as_secs_f64() / 1e6,
+        mem::size_of::<Dump<u8, W, B>>()
+    );
+}
+
+fn main() {
+    report::<8, u8>();
+    report::<16, u16>();
+    report::<32, u32>();
+
+    #[cfg(target_has_atomic = "64")]
+    report::<64, u64>();
+}
@@ -0,0 +1,34 @@
+//! [FreeListAlloc] as the global allocator.
+//!
+//! A few threads keep allocating and freeing strings and vectors of
+//! assorted sizes. Most of those allocations are served from the
+//! freelist once its buckets have warmed up.
+
+use std::thread;
+
+use freelist::FreeListAlloc;
+
+#[global_allocator]
+static ALLOC: FreeListAlloc<16> = FreeListAlloc::new();
+
+fn churn(seed: usize) -> usize {
+    let mut total = 0;
+
+    for round in 0..1000 {
+        let strings: Vec<String> = (0..16).map(|i| "x".repeat((seed + round + i) % 300)).collect();
+        let numbers: Vec<Vec<u64>> = (0..8).map(|i| vec![i as u64; (round * i) % 500]).collect();
+
+        total += strings.iter().map(String::len).sum::<usize>();
+        total += numbers.iter().map(Vec::len).sum::<usize>();
+    }
+
+    total
+}
+
+fn main() {
+    let handles: Vec<_> = (0..4).map(|seed| thread::spawn(move || churn(seed))).collect();
+    let total: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+
+    println!("allocated {} elements in total", total);
+    println!("{} blocks parked in the freelist", ALLOC.len());
+}
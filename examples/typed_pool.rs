@@ -0,0 +1,94 @@
+//! A pool of `Connection`s on top of a [FreeList].
+//!
+//! Every simulated request checks out a few connections. Their memory
+//! comes from the freelist when available, and goes back to it once
+//! the request is done with them.
+
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::mem::{align_of, size_of};
+use std::ptr;
+
+use freelist::{FreeList, CANARY_PADDING};
+
+struct Connection {
+    id: usize,
+    buffer: Vec<u8>,
+}
+
+static POOL: FreeList<Connection, 8> = FreeList::<_, 8>::new();
+
+#[derive(Default)]
+struct Stats {
+    hits: usize,
+    misses: usize,
+}
+
+/// Size of the bucket connections go to. Blocks are
+/// always allocated with this size so they can be reused.
+fn block_size() -> usize {
+    size_of::<Connection>().next_power_of_two()
+}
+
+/// Leaves room past the block for the canary of the `canary` feature.
+fn block_layout() -> Layout {
+    Layout::from_size_align(block_size() + CANARY_PADDING, align_of::<Connection>()).unwrap()
+}
+
+fn checkout(id: usize, stats: &mut Stats) -> *mut Connection {
+    let ptr = match POOL.recycle(block_size()) {
+        Ok(ptr) => {
+            stats.hits += 1;
+            ptr
+        }
+        Err(_) => {
+            stats.misses += 1;
+
+            let ptr = unsafe { alloc(block_layout()) };
+            if ptr.is_null() {
+                handle_alloc_error(block_layout());
+            }
+
+            ptr as *mut Connection
+        }
+    };
+
+    // Recycled memory holds no value, so it's written without dropping anything
+    unsafe {
+        ptr.write(Connection {
+            id,
+            buffer: Vec::with_capacity(64),
+        })
+    };
+
+    ptr
+}
+
+fn checkin(ptr: *mut Connection) {
+    // Only the memory is parked, the value itself is dropped here
+    unsafe { ptr::drop_in_place(ptr) };
+
+    if POOL.throw(ptr, block_size()).is_err() {
+        unsafe { dealloc(ptr as *mut u8, block_layout()) };
+    }
+}
+
+fn main() {
+    let mut stats = Stats::default();
+
+    for request in 0..100 {
+        let connections: Vec<_> = (0..request % 8 + 1).map(|id| checkout(id, &mut stats)).collect();
+
+        for &connection in &connections {
+            let connection = unsafe { &mut *connection };
+            connection
+                .buffer
+                .extend_from_slice(format!("request {} on {}", request, connection.id).as_bytes());
+        }
+
+        connections.into_iter().for_each(checkin);
+    }
+
+    println!("hits: {}, misses: {}", stats.hits, stats.misses);
+
+    POOL.clear(|ptr, _| unsafe { dealloc(ptr as *mut u8, block_layout()) });
+}
@@ -0,0 +1,51 @@
+//! Compile check that [FreeList] and [Dump] work in a `#![no_std]` crate.
+
+#![no_std]
+
+use freelist::{Dump, Error, FreeList};
+
+static FREELIST: FreeList<u8, 8> = FreeList::new();
+static DUMP: Dump<u8> = Dump::new();
+
+/// Parks `ptr` in the 64 byte bucket and takes it back out.
+pub fn round_trip(ptr: *mut u8) -> Result<*mut u8, Error> {
+    FREELIST.throw(ptr, 64)?;
+    FREELIST.recycle(64)
+}
+
+/// Same as [round_trip] on a bare [Dump].
+pub fn dump_round_trip(ptr: *mut u8) -> Option<*mut u8> {
+    DUMP.throw(ptr).ok()?;
+    DUMP.recycle()
+}
+
+/// A [Write](core::fmt::Write) into a fixed buffer, as there's no
+/// `String` without `alloc`. Whatever doesn't fit is an error.
+struct FixedBuf<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl core::fmt::Write for FixedBuf<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(core::fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Writes the `Display` of `error` into `buf` and returns it,
+/// or `None` if it doesn't fit.
+pub fn describe(error: Error, buf: &mut [u8]) -> Option<&str> {
+    use core::fmt::Write;
+
+    let mut out = FixedBuf { buf, len: 0 };
+    write!(out, "{}", error).ok()?;
+
+    let len = out.len;
+    core::str::from_utf8(&out.buf[..len]).ok()
+}
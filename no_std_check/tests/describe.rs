@@ -0,0 +1,31 @@
+use freelist::Error;
+use no_std_check::describe;
+
+#[test]
+fn every_error_is_displayed_without_std() {
+    let cases = [
+        (Error::BucketFull, "bucket is full"),
+        (Error::BucketNotAvailable, "bucket not available"),
+        (Error::BucketEmpty, "bucket is empty"),
+        (Error::SizeNotPowerOf2, "given size should be power of 2"),
+        (Error::Corrupted(0x1000), "canary past the block at 0x1000 was overwritten"),
+        (Error::Sealed, "freelist is sealed"),
+        (Error::SizeMismatch(0x20), "size doesn't match the one recorded for 0x20"),
+        (Error::Contended, "bucket is being used by other threads"),
+        (Error::ZeroSize, "size should not be 0"),
+        (Error::NullPointer, "pointer is null"),
+        (Error::DoubleThrow, "pointer is already in the bucket"),
+        (Error::BudgetExceeded, "freelist would go past its byte budget"),
+    ];
+
+    for (error, expected) in cases {
+        let mut buf = [0; 64];
+        assert_eq!(describe(error, &mut buf), Some(expected));
+    }
+}
+
+#[test]
+fn describe_fails_if_the_buffer_is_too_small() {
+    let mut buf = [0; 8];
+    assert_eq!(describe(Error::BucketFull, &mut buf), None);
+}
@@ -0,0 +1,185 @@
+//! Integers the bitmaps of a [Dump](crate::Dump) can be made of.
+
+#[cfg(target_has_atomic = "64")]
+use crate::sync::AtomicU64;
+use crate::sync::{AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+/// An unsigned integer that the bitmaps of a [Dump](crate::Dump)
+/// are made of. Each bit tracks a slot, so a dump can't hold more
+/// values than the integer has bits.
+///
+/// The default is `usize`, which gives 32 slots on 32 bit targets
+/// and 64 on 64 bit ones. With `u64`, dumps can have 64 slots on
+/// any target that has 64 bit atomics, e.g. `Dump<T, 64, u64>`.
+///
+/// Implemented for `u8`, `u16`, `u32`, `u64` and `usize`. It can't
+/// be implemented outside this crate, as the bitmaps are updated
+/// through atomics of the same width.
+pub trait BitmapInt: Bits {}
+
+pub(crate) use sealed::Bits;
+
+mod sealed {
+    use core::fmt::Debug;
+    use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+    use crate::sync::Ordering;
+
+    /// What [Dump](crate::Dump) needs from [BitmapInt](super::BitmapInt).
+    ///
+    /// The methods shadowed by the inherent ones of the integers are
+    /// only there so that they can be called on a generic bitmap.
+    pub trait Bits:
+        Copy + Eq + Debug + BitAnd<Output = Self> + BitAndAssign + BitOr<Output = Self> + BitOrAssign + Not<Output = Self>
+    {
+        /// Number of bits, i.e., the most slots a dump can have.
+        const BITS: u32;
+        /// No bit set.
+        const ZERO: Self;
+
+        /// Atomic integer of the same width.
+        type Atomic;
+
+        /// An atomic holding [Bits::ZERO].
+        #[cfg(not(loom))]
+        const EMPTY: Self::Atomic;
+
+        /// Same as [Bits::EMPTY], as loom's atomics can't be consts.
+        #[cfg(loom)]
+        fn empty() -> Self::Atomic;
+
+        /// Returns a bitmap with only the bit at `spot` set.
+        fn bit(spot: u32) -> Self;
+
+        fn trailing_zeros(self) -> u32;
+        fn trailing_ones(self) -> u32;
+        fn count_ones(self) -> u32;
+
+        fn load(atomic: &Self::Atomic, order: Ordering) -> Self;
+        fn store(atomic: &Self::Atomic, val: Self, order: Ordering);
+        fn compare_exchange_weak(
+            atomic: &Self::Atomic,
+            current: Self,
+            new: Self,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<Self, Self>;
+        fn fetch_and(atomic: &Self::Atomic, val: Self, order: Ordering) -> Self;
+        fn fetch_or(atomic: &Self::Atomic, val: Self, order: Ordering) -> Self;
+    }
+}
+
+macro_rules! impl_bitmap_int {
+    ($($(#[$attr:meta])* $int:ty => $atomic:ty,)*) => {$(
+        $(#[$attr])*
+        impl BitmapInt for $int {}
+
+        $(#[$attr])*
+        impl Bits for $int {
+            const BITS: u32 = <$int>::BITS;
+            const ZERO: Self = 0;
+
+            type Atomic = $atomic;
+
+            #[cfg(not(loom))]
+            #[allow(clippy::declare_interior_mutable_const)]
+            const EMPTY: $atomic = <$atomic>::new(0);
+
+            #[cfg(loom)]
+            fn empty() -> $atomic {
+                <$atomic>::new(0)
+            }
+
+            fn bit(spot: u32) -> Self {
+                1 << spot
+            }
+
+            fn trailing_zeros(self) -> u32 {
+                <$int>::trailing_zeros(self)
+            }
+
+            fn trailing_ones(self) -> u32 {
+                <$int>::trailing_ones(self)
+            }
+
+            fn count_ones(self) -> u32 {
+                <$int>::count_ones(self)
+            }
+
+            fn load(atomic: &$atomic, order: Ordering) -> Self {
+                atomic.load(order)
+            }
+
+            fn store(atomic: &$atomic, val: Self, order: Ordering) {
+                atomic.store(val, order)
+            }
+
+            fn compare_exchange_weak(
+                atomic: &$atomic,
+                current: Self,
+                new: Self,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<Self, Self> {
+                atomic.compare_exchange_weak(current, new, success, failure)
+            }
+
+            fn fetch_and(atomic: &$atomic, val: Self, order: Ordering) -> Self {
+                atomic.fetch_and(val, order)
+            }
+
+            fn fetch_or(atomic: &$atomic, val: Self, order: Ordering) -> Self {
+                atomic.fetch_or(val, order)
+            }
+        }
+    )*};
+}
+
+impl_bitmap_int! {
+    u8 => AtomicU8,
+    u16 => AtomicU16,
+    u32 => AtomicU32,
+    #[cfg(target_has_atomic = "64")]
+    u64 => AtomicU64,
+    usize => AtomicUsize,
+}
+
+/// A bitmap of `B` that is updated atomically, with the same
+/// methods as the atomic integers of core.
+pub(crate) struct AtomicBitmap<B: BitmapInt>(B::Atomic);
+
+impl<B: BitmapInt> AtomicBitmap<B> {
+    /// Returns a bitmap with no bit set.
+    #[cfg(not(loom))]
+    pub(crate) const fn new() -> Self {
+        AtomicBitmap(B::EMPTY)
+    }
+
+    #[cfg(loom)]
+    pub(crate) fn new() -> Self {
+        AtomicBitmap(B::empty())
+    }
+
+    pub(crate) fn load(&self, order: Ordering) -> B {
+        B::load(&self.0, order)
+    }
+
+    #[cfg(feature = "mutex-buckets")]
+    pub(crate) fn store(&self, val: B, order: Ordering) {
+        B::store(&self.0, val, order)
+    }
+
+    #[cfg(not(feature = "mutex-buckets"))]
+    pub(crate) fn compare_exchange_weak(&self, current: B, new: B, success: Ordering, failure: Ordering) -> Result<B, B> {
+        B::compare_exchange_weak(&self.0, current, new, success, failure)
+    }
+
+    pub(crate) fn fetch_and(&self, val: B, order: Ordering) -> B {
+        B::fetch_and(&self.0, val, order)
+    }
+
+    #[cfg(not(feature = "mutex-buckets"))]
+    pub(crate) fn fetch_or(&self, val: B, order: Ordering) -> B {
+        B::fetch_or(&self.0, val, order)
+    }
+}
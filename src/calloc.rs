@@ -1,20 +1,142 @@
+use std::cell::Cell;
+#[cfg(not(feature = "global-map"))]
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
+#[cfg(feature = "allocation-site")]
+use std::panic::Location;
+use std::ptr::null_mut;
+#[cfg(feature = "stats")]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use libc::{c_void, size_t};
+#[cfg(not(feature = "global-map"))]
 use once_cell::unsync::Lazy;
+use std::sync::{Mutex, PoisonError};
 
-use crate::freelist::{Error, FreeList};
+use crate::freelist::{Error, FreeList, CANARY_PADDING};
 
 thread_local! {
-    /// Mapping from pointer to size of memory
-    static MEMORY_MAP: Lazy<UnsafeCell<HashMap<*mut c_void, usize>>> = Lazy::new(|| {
+    /// Mapping from pointer address to its allocation
+    #[cfg(not(feature = "global-map"))]
+    static MEMORY_MAP: Lazy<UnsafeCell<HashMap<usize, Allocation>>> = Lazy::new(|| {
         UnsafeCell::new(HashMap::new())
     });
+
+    /// Set while this thread is inside [calloc] or [free]
+    static IN_CALL: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Mapping from pointer address to its allocation, shared by all threads
+/// so that a pointer can be freed on a different thread than the one
+/// that allocated it.
+#[cfg(feature = "global-map")]
+static MEMORY_MAP: once_cell::sync::Lazy<Mutex<HashMap<usize, Allocation>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Blocks that the freelist took out of circulation when they were
+/// recycled, as their canary was overwritten or their size didn't match.
+/// The wrappers that recycle don't get the underlying free, so
+/// [clear_freelist] frees these.
+static REJECTED: once_cell::sync::Lazy<Mutex<Vec<usize>>> = once_cell::sync::Lazy::new(Default::default);
+
+/// Runs `f` on `MEMORY_MAP`. Must only be called
+/// while holding a [ReentrancyGuard].
+#[cfg(not(feature = "global-map"))]
+fn with_memory_map<R>(f: impl FnOnce(&mut HashMap<usize, Allocation>) -> R) -> R {
+    MEMORY_MAP.with(|m| f(unsafe { m.get().as_mut().unwrap() }))
+}
+
+/// Runs `f` on `MEMORY_MAP`. Must only be called
+/// while holding a [ReentrancyGuard].
+#[cfg(feature = "global-map")]
+fn with_memory_map<R>(f: impl FnOnce(&mut HashMap<usize, Allocation>) -> R) -> R {
+    f(&mut MEMORY_MAP.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// What `MEMORY_MAP` knows about a pointer.
+struct Allocation {
+    /// Size of memory, i.e., the freelist bucket it goes to
+    size: usize,
+    /// Where [calloc] was called from
+    #[cfg(feature = "allocation-site")]
+    site: &'static Location<'static>,
+}
+
+/// Marks this thread as inside [calloc] or [free] until dropped.
+///
+/// `underlying_calloc`/`underlying_free` or the allocations made by
+/// `MEMORY_MAP` itself may call back into [calloc]/[free] (e.g. when
+/// these are used to back the global allocator). Such a nested call
+/// would alias the `&mut` to `MEMORY_MAP` that the outer call holds,
+/// or deadlock on its lock with `global-map` feature.
+/// So, nested calls bypass the freelist and `MEMORY_MAP` altogether.
+struct ReentrancyGuard;
+
+impl ReentrancyGuard {
+    /// Returns None if this thread is already inside [calloc] or [free]
+    /// (or if it's being torn down and its thread locals are gone).
+    fn enter() -> Option<ReentrancyGuard> {
+        match IN_CALL.try_with(|in_call| in_call.replace(true)) {
+            Ok(false) => Some(ReentrancyGuard),
+            Ok(true) | Err(_) => None,
+        }
+    }
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        let _ = IN_CALL.try_with(|in_call| in_call.set(false));
+    }
 }
 
 static FREELIST: FreeList<c_void, 11> = FreeList::<_, 11>::new();
 
+/// Requests smaller than this many bytes bypass the freelist and
+/// go straight to `underlying_calloc`, without being tracked.
+///
+/// For tiny allocations, the cost of tracking them in the thread
+/// local map can exceed what is saved by reusing them.
+///
+/// Set at build time with `FREELIST_CALLOC_MIN_CACHE_SIZE` environment
+/// variable. Defaults to 0, i.e., all sizes are cached.
+pub const CALLOC_MIN_CACHE_SIZE: usize = match option_env!("FREELIST_CALLOC_MIN_CACHE_SIZE") {
+    Some(size) => parse_size(size),
+    None => 0,
+};
+
+/// Parses a decimal number at compile time for [CALLOC_MIN_CACHE_SIZE].
+const fn parse_size(size: &str) -> usize {
+    let digits = size.as_bytes();
+    let mut parsed = 0;
+    let mut i = 0;
+
+    while i < digits.len() {
+        assert!(
+            digits[i].is_ascii_digit(),
+            "FREELIST_CALLOC_MIN_CACHE_SIZE should be a decimal number"
+        );
+        parsed = parsed * 10 + (digits[i] - b'0') as usize;
+        i += 1;
+    }
+
+    parsed
+}
+
+/// Number of times [clear_freelist] has completed.
+static CLEAR_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Number of [calloc] and [malloc] calls for each power of 2 size class,
+/// indexed by the power. See [size_histogram].
+#[cfg(feature = "stats")]
+static SIZE_HISTOGRAM: [AtomicUsize; usize::BITS as usize] = {
+    // Only used as operand of the array repeat expression below
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+    [ZERO; usize::BITS as usize]
+};
+
 /// A calloc wrapper that to make use of freelist. If freelist doesn't
 /// have any pointers, it will call `underlying_calloc()`.
 ///
@@ -25,56 +147,329 @@ static FREELIST: FreeList<c_void, 11> = FreeList::<_, 11>::new();
 /// local map. [free] would store this in freelist only if this thread local
 /// state has a mapping for it.
 ///
+/// Requests smaller than [CALLOC_MIN_CACHE_SIZE] are forwarded as they are
+/// and never tracked.
+///
+/// If `underlying_calloc` (or any allocation made while tracking the pointer)
+/// calls back into this function on the same thread, the nested call simply
+/// forwards to its own `underlying_calloc` without touching the freelist or
+/// the thread local map. Memory from such nested calls must be freed by
+/// nested [free] calls or directly by the underlying free.
+///
+/// With `global-map` feature, the map is shared by all threads instead, so
+/// a pointer allocated on one thread is reused even if it's freed on another.
+/// This costs a lock on every call.
+///
+/// With `allocation-site` feature, the location this is called from is
+/// recorded along with the pointer. See [allocation_site].
+///
+/// With `stats` feature, the requested size is counted in [size_histogram].
+///
+/// Returns null if `nmemb * size` overflows, without calling `underlying_calloc`.
+///
 /// NOTE: `underlying_calloc` is expected to allocate exactly what is asked from it.
+#[cfg_attr(feature = "allocation-site", track_caller)]
 pub fn calloc(nmemb: size_t, size: size_t, underlying_calloc: impl FnOnce(size_t, size_t) -> *mut c_void) -> *mut c_void {
-    let next_power_of_2 = (nmemb * size).next_power_of_two();
-    let mut new_nmemb = 1;
-    let mut new_size = next_power_of_2;
-    let mut recyclable = true;
-
-    let res = match FREELIST.recycle(next_power_of_2) {
-        Ok(ptr) => {
-            unsafe { ptr.write_bytes(0, nmemb * size) }; // calloc returns memory set to 0
-            Ok(ptr)
-        },
-        Err(Error::BucketFull) => unreachable!(),
-        Err(Error::BucketEmpty) => Err(()),
-        Err(Error::SizeNotPowerOf2 /* in case next_power_of_2() returns 0 */ | Error::BucketNotAvailable) => {
-            recyclable = false;
-            new_nmemb = nmemb;
-            new_size = size;
-            Err(())
-        },
+    let _guard = match ReentrancyGuard::enter() {
+        Some(guard) => guard,
+        None => return underlying_calloc(nmemb, size),
+    };
+
+    // Like C calloc, fail if the total size can't be represented
+    let total_size = match nmemb.checked_mul(size) {
+        Some(total_size) => total_size,
+        None => return null_mut(),
+    };
+
+    #[cfg(feature = "stats")]
+    if let Some(class) = total_size.checked_next_power_of_two() {
+        SIZE_HISTOGRAM[class.trailing_zeros() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    match find_block(total_size, 1) {
+        Block::Recycled(ptr, block_size) => {
+            unsafe { ptr.write_bytes(0, total_size) }; // calloc returns memory set to 0
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Allocate(block_size) => {
+            let ptr = underlying_calloc(1, block_size + CANARY_PADDING);
+
+            #[cfg(feature = "size-checks")]
+            crate::freelist::record_allocation_size(ptr, block_size);
+
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Untracked => underlying_calloc(nmemb, size),
+    }
+}
+
+/// Same as [calloc] but the returned memory is aligned to `align`,
+/// which must be a power of 2. Returns null if it's not.
+///
+/// The bucket is chosen for `max(nmemb * size, align)`, and only the
+/// pointers in it that are aligned to `align` are recycled. The rest
+/// are left for other requests. Blocks that can be reused are
+/// allocated with `underlying_aligned_calloc(size, align)`, which must
+/// return zeroed memory of `size` bytes aligned to `align`.
+/// They are freed with [free] as usual.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+pub fn aligned_calloc(
+    nmemb: size_t,
+    size: size_t,
+    align: size_t,
+    underlying_aligned_calloc: impl FnOnce(size_t, size_t) -> *mut c_void,
+) -> *mut c_void {
+    if !align.is_power_of_two() {
+        return null_mut();
+    }
+
+    let total_size = match nmemb.checked_mul(size) {
+        Some(total_size) => total_size,
+        None => return null_mut(),
+    };
+
+    let _guard = match ReentrancyGuard::enter() {
+        Some(guard) => guard,
+        None => return underlying_aligned_calloc(total_size, align),
+    };
+
+    match find_block(total_size, align) {
+        Block::Recycled(ptr, block_size) => {
+            unsafe { ptr.write_bytes(0, total_size) }; // calloc returns memory set to 0
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Allocate(block_size) => {
+            let ptr = underlying_aligned_calloc(block_size + CANARY_PADDING, align);
+
+            #[cfg(feature = "size-checks")]
+            crate::freelist::record_allocation_size(ptr, block_size);
+
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Untracked => underlying_aligned_calloc(total_size, align),
+    }
+}
+
+/// Same as [calloc] but for a single block of `size` bytes,
+/// and recycled memory isn't zeroed.
+///
+/// Blocks that can be reused are allocated with `underlying_malloc`
+/// given the size of their bucket, and are freed with [free] as usual.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+pub fn malloc(size: size_t, underlying_malloc: impl FnOnce(size_t) -> *mut c_void) -> *mut c_void {
+    let _guard = match ReentrancyGuard::enter() {
+        Some(guard) => guard,
+        None => return underlying_malloc(size),
+    };
+
+    #[cfg(feature = "stats")]
+    if let Some(class) = size.checked_next_power_of_two() {
+        SIZE_HISTOGRAM[class.trailing_zeros() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    match find_block(size, 1) {
+        Block::Recycled(ptr, block_size) => {
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Allocate(block_size) => {
+            let ptr = underlying_malloc(block_size + CANARY_PADDING);
+
+            #[cfg(feature = "size-checks")]
+            crate::freelist::record_allocation_size(ptr, block_size);
+
+            track(ptr, block_size);
+            ptr
+        }
+        Block::Untracked => underlying_malloc(size),
+    }
+}
+
+/// A realloc wrapper that makes use of freelist, see [calloc].
+///
+/// If `ptr` is tracked by this thread and `new_size` rounded up to the
+/// next power of 2 is the size of its bucket, `ptr` is returned as is.
+/// Otherwise, a block for `new_size` is recycled or allocated just like
+/// [calloc] does, with `underlying_realloc(null, size)` as the underlying
+/// allocator, the contents are copied over and `ptr` is released like
+/// [free] does with `underlying_free`. Recycled memory isn't zeroed.
+///
+/// If `ptr` is null, this allocates like [calloc] but without zeroing.
+/// If `ptr` isn't tracked by this thread, or this is a nested call, it
+/// is forwarded to `underlying_realloc` as it is.
+///
+/// Returns null if no memory could be allocated, in which case `ptr`
+/// is left untouched.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+pub fn realloc(
+    ptr: *mut c_void,
+    new_size: size_t,
+    underlying_realloc: impl Fn(*mut c_void, size_t) -> *mut c_void,
+    underlying_free: impl Fn(*mut c_void),
+) -> *mut c_void {
+    let _guard = match ReentrancyGuard::enter() {
+        Some(guard) => guard,
+        None => return underlying_realloc(ptr, new_size),
+    };
+
+    let old_size = if ptr.is_null() {
+        0
+    } else {
+        match with_memory_map(|m| m.get(&(ptr as usize)).map(|a| a.size)) {
+            Some(old_size) => old_size,
+            None => return underlying_realloc(ptr, new_size),
+        }
+    };
+
+    if !ptr.is_null() && new_size.checked_next_power_of_two() == Some(old_size) {
+        return ptr;
+    }
+
+    let new_ptr = match find_block(new_size, 1) {
+        Block::Recycled(new_ptr, block_size) => {
+            track(new_ptr, block_size);
+            new_ptr
+        }
+        Block::Allocate(block_size) => {
+            let new_ptr = underlying_realloc(null_mut(), block_size + CANARY_PADDING);
+
+            #[cfg(feature = "size-checks")]
+            crate::freelist::record_allocation_size(new_ptr, block_size);
+
+            track(new_ptr, block_size);
+            new_ptr
+        }
+        Block::Untracked => underlying_realloc(null_mut(), new_size),
+    };
+
+    if !ptr.is_null() && !new_ptr.is_null() {
+        unsafe { (ptr as *const u8).copy_to_nonoverlapping(new_ptr as *mut u8, old_size.min(new_size)) };
+        release(ptr, old_size, underlying_free);
+    }
+
+    new_ptr
+}
+
+/// Where the memory for a request of some size comes from.
+enum Block {
+    /// Recycled from the freelist, along with the bucket size
+    Recycled(*mut c_void, usize),
+    /// Should be allocated with the given bucket size
+    /// (plus [CANARY_PADDING]) to be reusable later
+    Allocate(usize),
+    /// Can't be reused, so should be allocated as it is
+    Untracked,
+}
+
+/// Tries to recycle a block for `size` bytes aligned to `align`
+/// from the freelist. `align` is 1 unless [aligned_calloc] is used.
+fn find_block(size: usize, align: usize) -> Block {
+    #[allow(clippy::absurd_extreme_comparisons)] // CALLOC_MIN_CACHE_SIZE is 0 by default
+    if size < CALLOC_MIN_CACHE_SIZE {
+        return Block::Untracked;
+    }
+
+    // Sizes above the largest power of 2 would wrap around to 0 in
+    // release builds. They are way too big for the freelist anyway.
+    let next_power_of_2 = match size.max(align).checked_next_power_of_two() {
+        Some(next_power_of_2) => next_power_of_2,
+        None => return Block::Untracked,
     };
 
-    let res = match res {
-        Ok(ptr) => ptr,
-        Err(_) => underlying_calloc(new_nmemb, new_size),
+    let res = if align > 1 {
+        FREELIST.recycle_aligned(next_power_of_2, align)
+    } else {
+        FREELIST.recycle(next_power_of_2)
     };
 
-    if recyclable {
-        MEMORY_MAP.with(|m| unsafe { m.get().as_mut().unwrap().insert(res, new_nmemb * new_size) });
+    match res {
+        Ok(ptr) => Block::Recycled(ptr, next_power_of_2),
+        Err(
+            Error::BucketFull
+            | Error::SizeNotPowerOf2
+            | Error::Contended
+            | Error::ZeroSize
+            | Error::NullPointer
+            | Error::DoubleThrow
+            | Error::BudgetExceeded,
+        ) => unreachable!(),
+        // A corrupted or mismatched block is left out of circulation until the next clear
+        Err(Error::Corrupted(addr) | Error::SizeMismatch(addr)) => {
+            with_memory_map(|m| m.remove(&addr));
+            REJECTED.lock().unwrap_or_else(PoisonError::into_inner).push(addr);
+
+            Block::Allocate(next_power_of_2)
+        }
+        Err(Error::BucketEmpty) => Block::Allocate(next_power_of_2),
+        Err(Error::BucketNotAvailable | Error::Sealed) => Block::Untracked,
     }
+}
 
-    res
+/// Records `ptr` in `MEMORY_MAP` so that it's reused once freed.
+/// Null pointers from failed allocations aren't recorded.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+fn track(ptr: *mut c_void, size: usize) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let allocation = Allocation {
+        size,
+        #[cfg(feature = "allocation-site")]
+        site: Location::caller(),
+    };
+
+    with_memory_map(|m| m.insert(ptr as usize, allocation));
+}
+
+/// Puts tracked `ptr` of `size` on the freelist, or frees it
+/// with `underlying_free` if the freelist can't take it.
+fn release(ptr: *mut c_void, size: usize, underlying_free: impl Fn(*mut c_void)) {
+    match FREELIST.throw(ptr, size) {
+        Ok(()) => {}
+        Err(
+            Error::BucketEmpty
+            | Error::BucketNotAvailable
+            | Error::SizeNotPowerOf2
+            | Error::Corrupted(_)
+            | Error::Contended
+            | Error::ZeroSize
+            // Null pointers are never tracked
+            | Error::NullPointer,
+        ) => unreachable!(),
+        // Freed twice, the block is parked already
+        Err(Error::DoubleThrow) => {}
+        Err(Error::BucketFull | Error::Sealed | Error::SizeMismatch(_) | Error::BudgetExceeded) => {
+            with_memory_map(|m| m.remove(&(ptr as usize)));
+
+            #[cfg(feature = "size-checks")]
+            crate::freelist::forget_allocation_size(ptr);
+
+            underlying_free(ptr)
+        }
+    }
 }
 
 /// A free wrapper that puts ptr on the freelist if it is reusable.
 /// If freelist is full or unusable, it simply calls `underlying_free`.
 ///
+/// Like [calloc], a nested call on the same thread goes straight to
+/// `underlying_free`.
+///
 /// See [calloc] for more info.
 pub fn free(ptr: *mut c_void, underlying_free: impl Fn(*mut c_void)) {
-    if let Some(&size) = MEMORY_MAP.with(|m| unsafe { m.get().as_ref().unwrap().get(&ptr) }) {
-        match FREELIST.throw(ptr, size) {
-            Ok(()) => {}
-            Err(Error::BucketEmpty | Error::BucketNotAvailable | Error::SizeNotPowerOf2) => unreachable!(),
-            Err(Error::BucketFull) => {
-                MEMORY_MAP.with(|m| unsafe { m.get().as_mut().unwrap().remove(&ptr) });
-                underlying_free(ptr)
-            }
-        };
-    } else {
-        underlying_free(ptr)
+    let _guard = match ReentrancyGuard::enter() {
+        Some(guard) => guard,
+        None => return underlying_free(ptr),
+    };
+
+    match with_memory_map(|m| m.get(&(ptr as usize)).map(|a| a.size)) {
+        Some(size) => release(ptr, size, underlying_free),
+        None => underlying_free(ptr),
     }
 }
 
@@ -84,7 +479,186 @@ pub fn free(ptr: *mut c_void, underlying_free: impl Fn(*mut c_void)) {
 /// So, if thread doesn't know about the pointer, it won't be reused and
 /// will just keep lying in the freelist.
 /// So, clear_freelist should be called periodically to make space
-/// for new pointers.
+/// for new pointers. This matters less with `global-map` feature.
+///
+/// Blocks that were found corrupted or mismatched when they were
+/// recycled are freed with `underlying_free` as well.
+///
+/// Freed blocks are forgotten by the map, so that their addresses
+/// aren't taken for reusable blocks once the underlying allocator
+/// hands them out again. Without `global-map` feature, only this
+/// thread's map can be updated.
 pub fn clear_freelist(underlying_free: impl Fn(*mut c_void)) {
-    FREELIST.clear(|ptr, _| underlying_free(ptr));
+    let guard = ReentrancyGuard::enter();
+
+    let free = |ptr| {
+        if guard.is_some() {
+            with_memory_map(|m| m.remove(&(ptr as usize)));
+        }
+
+        #[cfg(feature = "size-checks")]
+        crate::freelist::forget_allocation_size(ptr);
+
+        underlying_free(ptr)
+    };
+
+    FREELIST.clear(|ptr, _| free(ptr));
+
+    let rejected = std::mem::take(&mut *REJECTED.lock().unwrap_or_else(PoisonError::into_inner));
+    rejected.into_iter().for_each(|addr| free(addr as *mut c_void));
+
+    CLEAR_EPOCH.fetch_add(1, Ordering::Release);
+}
+
+/// The allocator that [calloc_with]/[free_with]/[clear_freelist_with]
+/// fall back to.
+///
+/// This is for allocators that are objects rather than bare functions,
+/// to avoid writing closures around them on every call.
+pub trait UnderlyingAlloc {
+    /// Allocates zeroed memory for `nmemb` elements of `size` bytes each.
+    /// Expected to allocate exactly what is asked from it.
+    fn calloc(&self, nmemb: size_t, size: size_t) -> *mut c_void;
+
+    /// Frees memory allocated by this allocator.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or allocated by this allocator,
+    /// and not freed since.
+    unsafe fn free(&self, ptr: *mut c_void);
+
+    /// Resizes memory allocated by this allocator to `size` bytes.
+    ///
+    /// # Safety
+    ///
+    /// Same as [UnderlyingAlloc::free]. `ptr` must not be used
+    /// afterwards unless null is returned.
+    unsafe fn realloc(&self, ptr: *mut c_void, size: size_t) -> *mut c_void;
+}
+
+/// [UnderlyingAlloc] that uses calloc/free/realloc from libc.
+pub struct LibcAlloc;
+
+impl UnderlyingAlloc for LibcAlloc {
+    fn calloc(&self, nmemb: size_t, size: size_t) -> *mut c_void {
+        unsafe { libc::calloc(nmemb, size) }
+    }
+
+    unsafe fn free(&self, ptr: *mut c_void) {
+        libc::free(ptr)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut c_void, size: size_t) -> *mut c_void {
+        libc::realloc(ptr, size)
+    }
+}
+
+/// Same as [calloc] with `alloc` as the underlying allocator.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+pub fn calloc_with(nmemb: size_t, size: size_t, alloc: &dyn UnderlyingAlloc) -> *mut c_void {
+    calloc(nmemb, size, |nmemb, size| alloc.calloc(nmemb, size))
+}
+
+/// Same as [realloc] with `alloc` as the underlying allocator.
+///
+/// # Safety
+///
+/// `ptr` must be null or allocated by [calloc_with] or [realloc_with]
+/// with the same `alloc`, and not freed since.
+#[cfg_attr(feature = "allocation-site", track_caller)]
+pub unsafe fn realloc_with(ptr: *mut c_void, new_size: size_t, alloc: &dyn UnderlyingAlloc) -> *mut c_void {
+    realloc(ptr, new_size, |ptr, size| alloc.realloc(ptr, size), |ptr| alloc.free(ptr))
+}
+
+/// Same as [free] with `alloc` as the underlying allocator.
+///
+/// # Safety
+///
+/// Same as [realloc_with].
+pub unsafe fn free_with(ptr: *mut c_void, alloc: &dyn UnderlyingAlloc) {
+    free(ptr, |ptr| alloc.free(ptr))
+}
+
+/// Same as [clear_freelist] with `alloc` as the underlying allocator.
+///
+/// # Safety
+///
+/// Every block on the freelist must have been allocated by `alloc`.
+pub unsafe fn clear_freelist_with(alloc: &dyn UnderlyingAlloc) {
+    clear_freelist(|ptr| alloc.free(ptr))
+}
+
+/// [calloc] on top of `libc::calloc`, with the signature of
+/// `mbedtls_calloc`. See [mbedtls_calloc_free].
+///
+/// # Safety
+///
+/// Same as `libc::calloc`.
+#[cfg(feature = "c-api")]
+#[no_mangle]
+pub unsafe extern "C" fn freelist_calloc(nmemb: size_t, size: size_t) -> *mut c_void {
+    calloc(nmemb, size, |nmemb, size| libc::calloc(nmemb, size))
+}
+
+/// [free] on top of `libc::free`, with the signature of
+/// `mbedtls_free`. See [mbedtls_calloc_free].
+///
+/// # Safety
+///
+/// `ptr` must be null or allocated by [freelist_calloc].
+#[cfg(feature = "c-api")]
+#[no_mangle]
+pub unsafe extern "C" fn freelist_free(ptr: *mut c_void) {
+    free(ptr, |ptr| libc::free(ptr))
+}
+
+/// Returns [freelist_calloc] and [freelist_free], ready to be
+/// passed to `mbedtls_platform_set_calloc_free`.
+#[cfg(feature = "c-api")]
+pub fn mbedtls_calloc_free() -> (
+    unsafe extern "C" fn(size_t, size_t) -> *mut c_void,
+    unsafe extern "C" fn(*mut c_void),
+) {
+    (freelist_calloc, freelist_free)
+}
+
+/// Returns the clear epoch, which is incremented every time
+/// [clear_freelist] completes.
+///
+/// When multiple subsystems share the freelist, they can poll
+/// this to know whether a sweep happened since they last looked.
+pub fn clear_epoch() -> u64 {
+    CLEAR_EPOCH.load(Ordering::Acquire)
+}
+
+/// Returns where [calloc] was called from to allocate `ptr`.
+///
+/// Only pointers tracked by this thread are known, i.e., the ones
+/// that [calloc] allocated on this thread and that can be reused.
+/// With `global-map` feature, the ones allocated on any thread.
+/// Useful to find out who allocated the pointers stuck in the freelist.
+#[cfg(feature = "allocation-site")]
+pub fn allocation_site(ptr: *mut c_void) -> Option<&'static Location<'static>> {
+    let _guard = ReentrancyGuard::enter()?;
+
+    with_memory_map(|m| m.get(&(ptr as usize)).map(|a| a.site))
+}
+
+/// Returns the number of [calloc] and [malloc] calls so far for each size, rounded
+/// up to the next power of 2, as `(size, count)` pairs in ascending
+/// order of size. Sizes that were never requested are left out.
+///
+/// This shows the actual distribution of allocation sizes, which
+/// helps choose the number of buckets of a [FreeList].
+///
+/// Nested calls described in [calloc] aren't counted.
+#[cfg(feature = "stats")]
+pub fn size_histogram() -> Vec<(usize, usize)> {
+    SIZE_HISTOGRAM
+        .iter()
+        .enumerate()
+        .map(|(power, count)| (1 << power, count.load(Ordering::Relaxed)))
+        .filter(|&(_, count)| count > 0)
+        .collect()
 }
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::freelist::FreeList;
+
+/// Holds a [FreeList] that can be replaced while in use, e.g. to
+/// swap in a freshly tuned one without downtime.
+///
+/// Users get the current freelist with [FreeListCell::load] and work
+/// on it as usual. A control thread replaces it with
+/// [FreeListCell::swap], after which new loads see the new freelist,
+/// while the ones already loaded keep working on the old one.
+pub struct FreeListCell<T, const N: usize> {
+    current: ArcSwap<FreeList<T, N>>,
+}
+
+impl<T, const N: usize> FreeListCell<T, N> {
+    /// Returns a cell holding `list`.
+    pub fn new(list: FreeList<T, N>) -> Self {
+        FreeListCell {
+            current: ArcSwap::from_pointee(list),
+        }
+    }
+
+    /// Returns the current freelist.
+    ///
+    /// It should be loaded again every now and then, e.g. for every
+    /// operation, so that a swapped out freelist doesn't stay in use.
+    pub fn load(&self) -> Arc<FreeList<T, N>> {
+        self.current.load_full()
+    }
+
+    /// Replaces the current freelist with `list` and returns the old one,
+    /// after draining and sealing it with [FreeList::drain_and_seal].
+    ///
+    /// Users still holding the old freelist get Sealed from then on,
+    /// so they free their blocks themselves instead of parking them,
+    /// and load the new freelist again.
+    ///
+    /// Operations on the old freelist that were in progress may still
+    /// park blocks after the drain. So, once the returned `Arc` is the
+    /// last one, the old freelist should be cleared again with `f`.
+    pub fn swap(&self, list: FreeList<T, N>, f: impl Fn(*mut T, usize)) -> Arc<FreeList<T, N>> {
+        let old = self.current.swap(Arc::new(list));
+
+        old.drain_and_seal(f);
+
+        old
+    }
+}
@@ -6,11 +6,78 @@
 // But I don't remember at all what was I trying to improve
 // and I surely didn't do any benchmarks.
 
-use std::cell::UnsafeCell;
-use std::ptr::null_mut;
-use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, null_mut};
+use core::sync::atomic::AtomicBool;
+#[cfg(feature = "stats")]
+use core::sync::atomic::{AtomicI64, AtomicU64};
+#[cfg(feature = "stats")]
+use core::time::Duration;
+#[cfg(feature = "size-checks")]
+use std::collections::HashMap;
+#[cfg(feature = "size-checks")]
+use std::sync::{Mutex, PoisonError};
+#[cfg(feature = "stats")]
+use std::time::Instant;
 
-use bit_fiddler::{set, unset};
+#[cfg(any(feature = "size-checks", feature = "stats"))]
+use once_cell::sync::Lazy;
+
+use crate::bitmap::{AtomicBitmap, BitmapInt};
+#[cfg(feature = "stats")]
+use crate::stats::FreeListStats;
+use crate::sync::{AtomicUsize, Ordering, UnsafeCell};
+
+/// Value written just past every parked block with `canary` feature.
+#[cfg(feature = "canary")]
+const CANARY: usize = usize::MAX / 0xFF * 0xA5;
+
+/// Extra bytes to allocate past each recyclable block for the
+/// canary written by [FreeList::throw], i.e., `size_of::<usize>()`
+/// with `canary` feature and 0 without it.
+///
+/// Allocating every block with `size + CANARY_PADDING` bytes works
+/// whether the feature is on or not.
+pub const CANARY_PADDING: usize = if cfg!(feature = "canary") {
+    core::mem::size_of::<usize>()
+} else {
+    0
+};
+
+/// Sizes recorded with [record_allocation_size], keyed by address.
+#[cfg(feature = "size-checks")]
+static ALLOCATION_SIZES: Lazy<Mutex<HashMap<usize, usize>>> = Lazy::new(Default::default);
+
+/// Records that `ptr` was allocated with `size` bytes.
+///
+/// With `size-checks` feature, [FreeList::throw] and [FreeList::recycle]
+/// return SizeMismatch if the size they're called with doesn't match
+/// the size recorded for the pointer. This catches blocks being parked
+/// in the wrong bucket, which would otherwise be read past their end
+/// once recycled.
+///
+/// As addresses get reused once the underlying allocator frees them,
+/// [forget_allocation_size] must be called before freeing the pointer.
+#[cfg(feature = "size-checks")]
+pub fn record_allocation_size<T>(ptr: *mut T, size: usize) {
+    ALLOCATION_SIZES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(ptr as usize, size);
+}
+
+/// Forgets the size recorded for `ptr` by [record_allocation_size].
+#[cfg(feature = "size-checks")]
+pub fn forget_allocation_size<T>(ptr: *mut T) {
+    ALLOCATION_SIZES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&(ptr as usize));
+}
 
 /// A freelist containing `N` buckets. These buckets store
 /// power of 2 sizes.
@@ -21,52 +88,113 @@ use bit_fiddler::{set, unset};
 /// 4) bucket for 8 byte (2^3)
 /// 5) bucket for 16 byte (2^4)
 ///
-/// Each bucket has a certain capacity to store pointers.
+/// Each bucket can store `W` pointers, `usize::BITS` by default.
+/// `W` can be raised up to the bits of `B`, see [BitmapInt].
 /// Generally, freelist is helpful when producer and consumer
 /// are both fast and ideally the size of each bucket shouldn't
-/// be kept very large in that case.
-///
-/// (For now, size of each bucket is fixed to size_of::<usize>() * 8
-/// but maybe configurable in future)
-pub struct FreeList<T, const N: usize>([Dump<T>; N]);
-
-macro_rules! impl_const_new {
-    ($n:literal) => {
-        impl<T> FreeList<T, $n> {
-            /// Initialize a freelist with empty buckets.
-            pub const fn new() -> FreeList<T, $n> {
-                FreeList(seq_macro::seq!(
-                    _ in 0..$n {
-                        [#(Dump::new(),)*]
-                    }
-                ))
-            }
-        }
-    };
-}
-
-impl_const_new!(1);
-impl_const_new!(2);
-impl_const_new!(3);
-impl_const_new!(4);
-impl_const_new!(5);
-impl_const_new!(6);
-impl_const_new!(7);
-impl_const_new!(8);
-impl_const_new!(9);
-impl_const_new!(10);
-impl_const_new!(11);
-impl_const_new!(12);
-impl_const_new!(13);
-impl_const_new!(14);
-impl_const_new!(15);
-impl_const_new!(16);
-impl_const_new!(17);
-impl_const_new!(18);
-impl_const_new!(19);
-impl_const_new!(20);
-
-impl<T, const N: usize> FreeList<T, N> {
+/// be kept very large in that case. So, a smaller `W` saves the
+/// memory of the slots that would never be used. See [Dump].
+// `repr(C)` keeps the layout the same for all `T`, see `FreeList::cast()`
+#[repr(C)]
+pub struct FreeList<T, const N: usize, const W: usize = { usize::BITS as usize }, B: BitmapInt = usize> {
+    pub(crate) buckets: [Dump<T, W, B>; N],
+    sealed: AtomicBool,
+    /// Bytes the freelist may hold, see [FreeList::set_max_bytes]
+    max_bytes: AtomicUsize,
+    /// Bytes parked in the buckets, see [FreeList::total_bytes_held]
+    bytes_held: AtomicUsize,
+}
+
+#[cfg(not(loom))]
+impl<T, const N: usize, const W: usize, B: BitmapInt> Default for FreeList<T, N, W, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, const W: usize, B: BitmapInt> FreeList<T, N, W, B> {
+    // Only used as operand of the array repeat expression in `new()`
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_DUMP: Dump<T, W, B> = Dump::new();
+
+    /// Size of the largest bucket, i.e., 2^(N - 1).
+    pub const MAX_SIZE: usize = 1 << (N - 1);
+
+    /// Maximum number of pointers the freelist can hold over all
+    /// its buckets, i.e., `N * W`, e.g. 704 for 11 buckets on a
+    /// 64 bit target with the default `W`.
+    pub const TOTAL_CAPACITY: usize = N * W;
+
+    /// Initialize a freelist with empty buckets.
+    ///
+    /// `N` can't be more than `usize::BITS`, e.g. 32 on 32 bit targets,
+    /// as the sizes of the buckets wouldn't fit in a `usize` otherwise.
+    ///
+    /// Not available when built with `--cfg loom`, use [FreeList::new_in_place].
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        Self::assert_bucket_count();
+
+        FreeList {
+            buckets: [Self::EMPTY_DUMP; N],
+            sealed: AtomicBool::new(false),
+            max_bytes: AtomicUsize::new(usize::MAX),
+            bytes_held: AtomicUsize::new(0),
+        }
+    }
+
+    /// Initializes a freelist with empty buckets at `ptr`, without
+    /// building it on the stack first like [FreeList::new] does.
+    ///
+    /// This is for big freelists in memory that custom allocators
+    /// manage themselves, e.g. `mmap`ed memory. Each bucket is written
+    /// in place one after the other.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned for `FreeList<T, N, W, B>`.
+    /// Whatever was there is overwritten without being dropped. Once
+    /// this returns, the memory holds a freelist that can be used
+    /// through `&*ptr` for as long as the memory stays valid.
+    pub unsafe fn new_in_place(ptr: *mut Self) {
+        Self::assert_bucket_count();
+
+        let buckets = ptr::addr_of_mut!((*ptr).buckets) as *mut Dump<T, W, B>;
+
+        for idx in 0..N {
+            buckets.add(idx).write(Dump::new());
+        }
+
+        ptr::addr_of_mut!((*ptr).sealed).write(AtomicBool::new(false));
+        ptr::addr_of_mut!((*ptr).max_bytes).write(AtomicUsize::new(usize::MAX));
+        ptr::addr_of_mut!((*ptr).bytes_held).write(AtomicUsize::new(0));
+    }
+
+    /// See [FreeList::new].
+    const fn assert_bucket_count() {
+        assert!(N <= usize::BITS as usize, "bucket sizes should fit in a usize");
+    }
+
+    /// Returns [FreeList::TOTAL_CAPACITY].
+    ///
+    /// ```
+    /// use freelist::FreeList;
+    ///
+    /// assert_eq!(FreeList::<u8, 4>::new().capacity(), 4 * usize::BITS as usize);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        Self::TOTAL_CAPACITY
+    }
+
+    /// Returns the number of buckets, i.e., `N`. Their sizes
+    /// are `1 << 0` to `1 << (N - 1)`.
+    ///
+    /// For code that can't name `N`, e.g. behind a trait object.
+    pub fn bucket_count(&self) -> usize {
+        N
+    }
+
     /// Expects a size which is power of 2 and returns
     /// a pointer if available in freelist.
     ///
@@ -74,336 +202,2414 @@ impl<T, const N: usize> FreeList<T, N> {
     /// Returns BucketEmpty is nothing is available.
     /// Returns BucketNotAvailable is bucket for the given
     /// size doesn't exist.
+    /// Returns Sealed if the freelist has been sealed.
+    ///
+    /// With `canary` feature, returns Corrupted if the canary
+    /// written by [FreeList::throw] was overwritten while the
+    /// block was parked. Such a block is dropped from the
+    /// freelist and never handed out, but its address is in
+    /// the error so that the caller can free it.
+    ///
+    /// With `size-checks` feature, returns SizeMismatch if `size`
+    /// doesn't match the one recorded by [record_allocation_size]
+    /// for the pointer. Such a block is also dropped from the freelist
+    /// and its address is in the error.
     pub fn recycle(&self, size: usize) -> Result<*mut T, Error> {
-        if !size.is_power_of_two() {
-            return Err(Error::SizeNotPowerOf2);
-        }
+        self.check_sealed()?;
 
-        let power = size.trailing_zeros();
+        let ptr = self.recycle_from(Self::bucket_index(size)?).ok_or(Error::BucketEmpty)?;
 
-        if power < N as u32 {
-            self.0[power as usize].recycle().ok_or(Error::BucketEmpty)
-        } else {
-            Err(Error::BucketNotAvailable)
-        }
+        Self::check_recycled(ptr, size)
     }
 
-    /// Throws the given pointer into the freelist.
+    /// Recycles a pointer from the bucket at `idx`, taking
+    /// its bytes off [FreeList::total_bytes_held].
+    fn recycle_from(&self, idx: usize) -> Option<*mut T> {
+        let ptr = self.buckets[idx].recycle()?;
+        self.release_bytes(idx, 1);
+
+        Some(ptr)
+    }
+
+    /// Takes `count` blocks of the bucket at `idx` off
+    /// [FreeList::total_bytes_held], once they're out of the bucket.
+    fn release_bytes(&self, idx: usize, count: usize) {
+        self.bytes_held.fetch_sub(count << idx, Ordering::Relaxed);
+    }
+
+    /// Same as [FreeList::recycle] but gives up after a single attempt
+    /// if other threads are using the bucket at the same time, in
+    /// which case Contended is returned.
     ///
-    /// Returns SizeNotPowerOf2 if `size` is not power of 2
-    /// Returns BucketFull if the corresponding bucket is full.
-    /// Returns BucketNotAvailable is bucket for the given
-    /// size doesn't exist.
-    pub fn throw(&self, ptr: *mut T, size: usize) -> Result<(), Error> {
-        if !size.is_power_of_two() {
-            return Err(Error::SizeNotPowerOf2);
-        }
+    /// See [Dump::try_recycle_once].
+    pub fn try_recycle_once(&self, size: usize) -> Result<*mut T, Error> {
+        self.check_sealed()?;
 
-        let power = size.trailing_zeros();
+        let idx = Self::bucket_index(size)?;
+        let ptr = self.buckets[idx].try_recycle_once().map_err(|err| match err {
+            RecycleError::Empty => Error::BucketEmpty,
+            RecycleError::Contended => Error::Contended,
+        })?;
+        self.release_bytes(idx, 1);
 
-        if power < N as u32 {
-            self.0[power as usize].throw(ptr).map_err(|_| Error::BucketFull)
-        } else {
-            Err(Error::BucketNotAvailable)
+        Self::check_recycled(ptr, size)
+    }
+
+    /// Gets up to `want` pointers from the bucket for `size` into
+    /// `local` in one batched operation and returns how many were added.
+    ///
+    /// This lets a consumer keep a thread local cache of blocks and
+    /// only come back to the freelist when the cache runs out.
+    /// Blocks failing the checks described in [FreeList::recycle]
+    /// are dropped from the freelist and passed to `reject` along
+    /// with the error instead of being added to `local`, so that
+    /// the caller can free them.
+    ///
+    /// See [Dump::recycle_into].
+    #[cfg(feature = "alloc")]
+    pub fn recycle_refill(
+        &self,
+        size: usize,
+        local: &mut Vec<*mut T>,
+        want: usize,
+        mut reject: impl FnMut(*mut T, Error),
+    ) -> Result<usize, Error> {
+        self.check_sealed()?;
+
+        let idx = Self::bucket_index(size)?;
+        let old_len = local.len();
+
+        let got = self.buckets[idx].recycle_into(local, want);
+        self.release_bytes(idx, got);
+
+        let mut i = old_len;
+        while i < local.len() {
+            match Self::check_recycled(local[i], size) {
+                Ok(_) => i += 1,
+                Err(err) => reject(local.swap_remove(i), err),
+            }
         }
+
+        Ok(local.len() - old_len)
     }
 
-    /// Clears the freelist.
+    /// Frees parked blocks, largest bucket first, until at least
+    /// `target_bytes` have been freed or the freelist runs dry.
+    /// Returns the number of bytes actually freed.
     ///
-    /// f(ptr, bucket_size)
+    /// f(ptr, size)
     ///   ptr:
     ///     ptr to free
-    ///   bucket_size:
-    ///     power of 2. For example, if
-    ///     this value is 4, size to free
-    ///     is 16.
-    pub fn clear(&self, f: impl Fn(*mut T, usize)) {
-        for (idx, dump) in self.0.iter().enumerate() {
-            dump.clear(|ptr| f(ptr, idx))
+    ///   size:
+    ///     size of the block in bytes, unlike
+    ///     [FreeList::clear] which passes the power.
+    ///
+    /// Unlike [FreeList::clear], blocks are recycled one at a time,
+    /// so this stops as soon as the target is met.
+    pub fn reclaim_until(&self, target_bytes: usize, f: impl Fn(*mut T, usize)) -> usize {
+        let mut reclaimed = 0usize;
+
+        for idx in (0..N).rev() {
+            while reclaimed < target_bytes {
+                match self.recycle_from(idx) {
+                    Some(ptr) => {
+                        f(ptr, 1 << idx);
+                        reclaimed = reclaimed.saturating_add(1 << idx);
+                    }
+                    None => break,
+                }
+            }
         }
+
+        reclaimed
     }
 
-    /// Clears bucket for the particular size.
-    pub fn clear_bucket(&self, size: usize, f: impl Fn(*mut T)) -> Result<(), Error> {
-        if !size.is_power_of_two() {
-            return Err(Error::SizeNotPowerOf2);
-        }
+    /// Same as [FreeList::recycle] but if the bucket for `size` is empty,
+    /// goes on to the bigger buckets, scanning at most `max_buckets_to_scan`
+    /// buckets in total. Returns the pointer along with the size of
+    /// the bucket it came from.
+    ///
+    /// The limit bounds the latency of a single call when the freelist
+    /// is under pressure. Returns BucketEmpty if nothing was found within
+    /// it, even though a bigger bucket beyond it may have a pointer.
+    pub fn recycle_within(&self, size: usize, max_buckets_to_scan: usize) -> Result<(*mut T, usize), Error> {
+        self.check_sealed()?;
 
-        let power = size.trailing_zeros();
+        let first = Self::bucket_index(size)?;
 
-        if power < N as u32 {
-            self.0[power as usize].clear(f);
-            Ok(())
-        } else {
-            Err(Error::BucketNotAvailable)
+        for idx in (first..N).take(max_buckets_to_scan) {
+            if let Some(ptr) = self.recycle_from(idx) {
+                let bucket_size = 1 << idx;
+
+                return Self::check_recycled(ptr, bucket_size).map(|ptr| (ptr, bucket_size));
+            }
         }
-    }
-}
 
-#[derive(Debug, Clone)]
-/// Error thrown by methods of FreeList
-pub enum Error {
-    /// The bucket for the requested size is full.
-    /// Memory can't be stored on the freelist.
-    BucketFull,
-    /// Bucket for the given size is not available.
-    /// Can't be stored or fetched from the freelist.
-    BucketNotAvailable,
-    /// The bucket for the requested size is empty.
-    /// Basically, memory can't be obtained from freelist.
-    BucketEmpty,
-    /// The freelist only supports power of 2
-    /// sizes. The calling code needs to handle
-    /// going to next power of 2 if needed.
-    SizeNotPowerOf2,
-}
+        Err(Error::BucketEmpty)
+    }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match self {
-            Error::BucketFull => write!(f, "bucket is full"),
-            Error::BucketNotAvailable => write!(f, "bucket not available"),
-            Error::BucketEmpty => write!(f, "bucket is empty"),
-            Error::SizeNotPowerOf2 => write!(f, "given size should be power of 2"),
-        }
+    /// Same as [FreeList::recycle] but if the bucket for `size` is empty,
+    /// the first bigger bucket that has a pointer is used instead.
+    /// Returns the pointer along with the size of the bucket it came from.
+    ///
+    /// The returned block may be bigger than `size`. The caller is
+    /// responsible for keeping track of the returned size, as that's
+    /// the size it must be thrown back with.
+    ///
+    /// Same as [FreeList::recycle_within] with no limit on the scan.
+    pub fn recycle_or_larger(&self, size: usize) -> Result<(*mut T, usize), Error> {
+        self.recycle_within(size, N)
     }
-}
 
-impl std::error::Error for Error {}
+    /// Same as [FreeList::recycle] but `size` doesn't need to be a power
+    /// of 2. It's rounded up to the next power of 2, which is returned
+    /// along with the pointer as the actual size of the block.
+    ///
+    /// Returns ZeroSize if `size` is 0, and BucketNotAvailable if
+    /// `size` is too big to be rounded up.
+    pub fn recycle_at_least(&self, size: usize) -> Result<(*mut T, usize), Error> {
+        if size == 0 {
+            return Err(Error::ZeroSize);
+        }
 
-/// In this struct,
-/// max_bits!(reader_bitmap) == max_bits!(writer_bitmap) == dump.len()
-///
-/// The accesses to dump[] array are synchronized by reader_bitmap
-/// and writer_bitmap.
-///
-/// Max possible length is (sizeof(usize) * 8) which is actually
-/// all what is needed as such a structure is meant for cases
-/// where producer and consumer are equally fast.
-/// Otherwise also, it isn't generally required to keep a lot
-/// of memory unfreed.
-pub struct Dump<T> {
-    reader_bitmap: AtomicUsize,
-    writer_bitmap: AtomicUsize,
-    dump: UnsafeCell<[*mut T; usize::BITS as usize]>,
-}
+        let rounded_size = size.checked_next_power_of_two().ok_or(Error::BucketNotAvailable)?;
 
-unsafe impl<T> Send for Dump<T> {}
-unsafe impl<T> Sync for Dump<T> {}
+        self.recycle(rounded_size).map(|ptr| (ptr, rounded_size))
+    }
 
-impl<T> Dump<T> {
-    /// Returns a new Dump instance.
+    /// Returns a block of at least `size` bytes along with its actual
+    /// size, which is `size` rounded up to the next power of 2.
     ///
-    /// ```ignore
+    /// The block is recycled from the freelist if possible. Otherwise,
+    /// e.g. if the bucket is empty or the size is too big for any
+    /// bucket, it comes from `alloc(actual_size)`. Blocks that `alloc`
+    /// returns must meet the requirements of [FreeList::throw] if they
+    /// are going to be thrown later.
     ///
-    /// struct Example {
-    ///     a: i32,
-    ///     b: String,
-    /// }
+    /// If `size` can't be rounded up, `alloc(size)` is called as is.
     ///
-    /// let dump = Dump::<Example>::new();
-    /// ```
-    pub const fn new() -> Self {
-        Dump {
-            reader_bitmap: AtomicUsize::new(0),
-            writer_bitmap: AtomicUsize::new(0),
-            dump: UnsafeCell::new([null_mut::<T>(); usize::BITS as usize]),
+    /// A block that [FreeList::recycle] takes out of the bucket but
+    /// rejects, i.e. Corrupted or SizeMismatch, is passed to `free`
+    /// before falling back to `alloc`, so that it doesn't leak.
+    pub fn get_or_alloc(&self, size: usize, alloc: impl FnOnce(usize) -> *mut T, free: impl FnOnce(*mut T)) -> (*mut T, usize) {
+        let rounded_size = match size.checked_next_power_of_two() {
+            Some(rounded_size) => rounded_size,
+            None => return (alloc(size), size),
+        };
+
+        match self.recycle(rounded_size) {
+            Ok(ptr) => (ptr, rounded_size),
+            Err(Error::Corrupted(addr) | Error::SizeMismatch(addr)) => {
+                free(addr as *mut T);
+
+                (alloc(rounded_size), rounded_size)
+            }
+            Err(_) => (alloc(rounded_size), rounded_size),
         }
     }
 
-    /// Adds a new element to the dump. On success it returns
-    /// () and on failure returns back the ptr indicating
-    /// that it couldn't be stored.
+    /// Same as [FreeList::recycle] but also returns the power of 2
+    /// of the bucket the pointer came from, e.g. 4 for size 16.
     ///
-    /// To synchronize this addition to the dump[] array, the following
-    /// procedure is followed:
+    /// This is the same index that [FreeList::clear] passes to its
+    /// closure, for callers that keep track of powers rather than bytes.
+    pub fn recycle_with_power(&self, size: usize) -> Result<(*mut T, u32), Error> {
+        self.recycle(size).map(|ptr| (ptr, size.trailing_zeros()))
+    }
+
+    /// Same as [FreeList::recycle] but also returns the index of the
+    /// slot in the bucket that the pointer came from.
     ///
-    /// 1) It checks `writer_bitmap` for unset bits (0 bits).
-    /// 2) When it finds one, it atomically sets it.
-    /// 3) We use this bit position as the index in `dump[]` to store the value.
-    /// 4) Setting the bit in `writer_bitmap` ensures that no
-    ///    other thread will write at that index.
-    /// 5) After storing `raw` in the `dump[]`, we tell reader threads
-    ///    that this index is available for read. To do this, we set this
-    ///    same bit position in `reader_bitmap` atomically.
-    pub fn throw(&self, raw: *mut T) -> Result<(), *mut T> {
-        let mut old_writer_bitmap = self.writer_bitmap.load(Ordering::Relaxed);
-        let mut first_empty_spot;
+    /// See [Dump::recycle_with_slot].
+    pub fn recycle_with_slot(&self, size: usize) -> Result<(*mut T, u32), Error> {
+        self.check_sealed()?;
 
-        loop {
-            // basically returns the first bit which is 0
-            first_empty_spot = old_writer_bitmap.trailing_ones();
+        let idx = Self::bucket_index(size)?;
+        let (ptr, slot) = self.buckets[idx].recycle_with_slot().ok_or(Error::BucketEmpty)?;
+        self.release_bytes(idx, 1);
 
-            // occupy `first_empty_spot` in `old_writer_bitmap` and assign it to `new_writer_bitmap`
-            let new_writer_bitmap = if first_empty_spot == usize::BITS {
-                return Err(raw);
-            } else {
-                set!(old_writer_bitmap, usize, first_empty_spot)
-            };
+        Self::check_recycled(ptr, size).map(|ptr| (ptr, slot))
+    }
 
-            match self.writer_bitmap.compare_exchange_weak(
-                old_writer_bitmap,
-                new_writer_bitmap,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_writer_bitmap = old,
-            };
-        }
+    /// Same as [FreeList::recycle] but also returns a [ThrowToken]
+    /// for the bucket the pointer came from. Passing it back to
+    /// [FreeList::throw_checked_in] guarantees that the pointer
+    /// goes back to the same bucket.
+    pub fn recycle_checked_out(&self, size: usize) -> Result<(*mut T, ThrowToken), Error> {
+        self.recycle_with_power(size).map(|(ptr, power)| (ptr, ThrowToken { power }))
+    }
 
-        let dump_ptr = self.dump.get();
+    /// Throws `ptr` back into the bucket it was taken out
+    /// of by [FreeList::recycle_checked_out].
+    ///
+    /// Same errors as [FreeList::throw].
+    pub fn throw_checked_in(&self, ptr: *mut T, token: ThrowToken) -> Result<(), Error> {
+        self.throw(ptr, token.size())
+    }
 
-        unsafe {
-            (*dump_ptr)[first_empty_spot as usize] = raw;
-        }
+    /// Same as [FreeList::recycle] but only gets a pointer if the bucket
+    /// holds more than `min_keep` pointers, so that a warm reserve is
+    /// always kept for latency sensitive callers. Returns Ok(None) if
+    /// the bucket doesn't have more than `min_keep` pointers.
+    ///
+    /// See [Dump::recycle_if_above].
+    pub fn recycle_if_above(&self, size: usize, min_keep: usize) -> Result<Option<*mut T>, Error> {
+        self.check_sealed()?;
 
-        let mut old_reader_bitmap = self.reader_bitmap.load(Ordering::Relaxed);
+        let idx = Self::bucket_index(size)?;
 
-        loop {
-            let new_reader_bitmap = set!(old_reader_bitmap, usize, first_empty_spot);
-
-            /*
-             * Memory order on success should be `Ordering::Release`.
-             * If it was Ordering::Relaxed, it would become possible
-             * that `recycle()` sees this bit as set in `reader_bitmap`
-             * but doesn't see the newly updated value in `dump[]`.
-             */
-            match self.reader_bitmap.compare_exchange_weak(
-                old_reader_bitmap,
-                new_reader_bitmap,
-                Ordering::Release,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_reader_bitmap = old,
-            };
+        match self.buckets[idx].recycle_if_above(min_keep) {
+            Some(ptr) => {
+                self.release_bytes(idx, 1);
+                Self::check_recycled(ptr, size).map(Some)
+            }
+            None => Ok(None),
         }
-
-        Ok(())
     }
 
-    /// Gets a value from the dump. On success it returns
-    /// the value `*mut T` and on failure (). Failure indicates
-    /// that dump is empty.
+    /// Same as [FreeList::recycle] but only gets a pointer aligned
+    /// to `align`. Pointers in the bucket that aren't aligned are
+    /// left there. Returns BucketEmpty if no aligned pointer is found.
     ///
-    /// To synchronize the retreival from the dump[] array, the following
-    /// procedure is followed:
+    /// Returns SizeNotPowerOf2 if `align` is not a power of 2.
     ///
-    /// 1) A set bit is searched in `reader_bitmap` and then we
-    ///    atomically unset that bit in `reader_bitmap`.
-    /// 2) Corresponding to the bit posn that we unset, we get the
-    ///    `dump[bit_posn]`.
-    /// 3) Then to allow writers to use this position for new writes,
-    ///    we unset this bit from `writer_bitmap`.
-    /// 4) Finally, we return `dump[bit_posn]`.
-    pub fn recycle(&self) -> Option<*mut T> {
-        let mut old_reader_bitmap = self.reader_bitmap.load(Ordering::Relaxed);
-        let mut first_set_spot;
+    /// See [Dump::recycle_matching].
+    pub fn recycle_aligned(&self, size: usize, align: usize) -> Result<*mut T, Error> {
+        self.check_sealed()?;
 
-        loop {
-            // basically returns the first bit which is 1
-            first_set_spot = old_reader_bitmap.trailing_zeros();
+        if !align.is_power_of_two() {
+            return Err(Error::SizeNotPowerOf2);
+        }
 
-            // occupy `first_set_spot` in `old_reader_bitmap` and assign it to `new_reader_bitmap`
-            let new_reader_bitmap = if first_set_spot == usize::BITS {
-                return None;
-            } else {
-                unset!(old_reader_bitmap, usize, first_set_spot)
-            };
+        let idx = Self::bucket_index(size)?;
+        let ptr = self.buckets[idx]
+            .recycle_matching(|ptr| ptr as usize % align == 0)
+            .ok_or(Error::BucketEmpty)?;
+        self.release_bytes(idx, 1);
 
-            match self.reader_bitmap.compare_exchange_weak(
-                old_reader_bitmap,
-                new_reader_bitmap,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_reader_bitmap = old,
-            };
-        }
+        Self::check_recycled(ptr, size)
+    }
 
-        let dump_ptr = self.dump.get();
+    /// Same as [FreeList::recycle] but for a block that fits `layout`.
+    ///
+    /// The bucket is the one for `layout.size().max(layout.align())`
+    /// rounded up to the next power of 2. As the blocks in a bucket
+    /// needn't be aligned to more than what they were allocated with,
+    /// only the ones aligned to `layout.align()` are recycled, see
+    /// [FreeList::recycle_aligned].
+    pub fn recycle_layout(&self, layout: Layout) -> Result<*mut T, Error> {
+        self.recycle_aligned(Self::layout_bucket_size(layout)?, layout.align())
+    }
 
-        let retval = unsafe { (*dump_ptr)[first_set_spot as usize] };
+    /// Throws a block that was allocated for `layout` into the bucket
+    /// that [FreeList::recycle_layout] recycles it from.
+    ///
+    /// Same errors as [FreeList::throw].
+    pub fn throw_layout(&self, ptr: *mut T, layout: Layout) -> Result<(), Error> {
+        self.throw(ptr, Self::layout_bucket_size(layout)?)
+    }
 
-        let mut old_writer_bitmap = self.writer_bitmap.load(Ordering::Relaxed);
+    /// Returns the size of the bucket that blocks for `layout` go to.
+    fn layout_bucket_size(layout: Layout) -> Result<usize, Error> {
+        layout
+            .size()
+            .max(layout.align())
+            .checked_next_power_of_two()
+            .ok_or(Error::BucketNotAvailable)
+    }
 
-        loop {
-            let new_writer_bitmap = unset!(old_writer_bitmap, usize, first_set_spot);
-
-            match self.writer_bitmap.compare_exchange_weak(
-                old_writer_bitmap,
-                new_writer_bitmap,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_writer_bitmap = old,
-            };
+    /// Throws the given pointer into the freelist.
+    ///
+    /// Returns SizeNotPowerOf2 if `size` is not power of 2
+    /// Returns BucketFull if the corresponding bucket is full.
+    /// Returns BucketNotAvailable is bucket for the given
+    /// size doesn't exist.
+    /// Returns Sealed if the freelist has been sealed.
+    /// Returns NullPointer if `ptr` is null.
+    /// Returns BudgetExceeded if holding `ptr` too would take the
+    /// freelist past [FreeList::set_max_bytes].
+    ///
+    /// With `debug-checks` feature, returns DoubleThrow if `ptr`
+    /// is already in the bucket.
+    ///
+    /// With `canary` feature, a canary value is written just past
+    /// the block, i.e., at `ptr + size`, to catch buffer overruns
+    /// into parked blocks. So, every block thrown must actually be
+    /// at least `size + size_of::<usize>()` bytes.
+    ///
+    /// With `size-checks` feature, returns SizeMismatch if `size`
+    /// doesn't match the one recorded by [record_allocation_size].
+    ///
+    /// The slots reserved with [FreeList::set_reserved_slots] are
+    /// left alone, see [FreeList::throw_priority].
+    pub fn throw(&self, ptr: *mut T, size: usize) -> Result<(), Error> {
+        self.throw_into(ptr, size, false)
+    }
+
+    /// Counterpart of [FreeList::get_or_alloc]. Rounds `size` up to
+    /// the next power of 2 and throws `ptr` into the freelist. If that
+    /// fails for any reason, e.g. the bucket is full or doesn't exist,
+    /// `free(ptr)` is called instead so that it doesn't leak.
+    ///
+    /// With `debug-checks` feature, a `ptr` that is already in the
+    /// bucket isn't passed to `free`, as the freelist still holds it.
+    ///
+    /// Returns true if `ptr` was retained by the freelist and
+    /// false if it was passed to `free`.
+    pub fn put_or_free(&self, ptr: *mut T, size: usize, free: impl FnOnce(*mut T)) -> bool {
+        let retained = match size.checked_next_power_of_two() {
+            Some(rounded_size) => matches!(self.throw(ptr, rounded_size), Ok(()) | Err(Error::DoubleThrow)),
+            None => false,
+        };
+
+        if !retained {
+            free(ptr);
         }
 
-        Some(retval)
+        retained
     }
 
-    /// This executes closure `f` for every value in the dump
-    /// and clears the dump.
+    /// [FreeList::get_or_alloc] that returns the block in a guard,
+    /// which gives it back with [FreeList::put_or_free] when dropped.
+    /// So, `free` is only called for the block if the freelist
+    /// can't take it back, e.g. because the bucket is full.
     ///
-    /// Does the following:
-    /// - Tries to replace reader bitmap with 0
-    /// - Calls f() for each index that was set as per the bitmap.
-    /// - Sets writer bitmap to 0.
-    pub fn clear(&self, f: impl Fn(*mut T)) {
-        let mut old_reader_bitmap = self.reader_bitmap.load(Ordering::Relaxed);
-        let new_reader_bitmap = 0;
+    /// `free` is also called for a recycled block that was rejected,
+    /// see [FreeList::get_or_alloc].
+    pub fn checkout<F: Fn(*mut T)>(
+        &self,
+        size: usize,
+        alloc: impl FnOnce(usize) -> *mut T,
+        free: F,
+    ) -> Recycled<'_, T, F, N, W, B> {
+        let (ptr, size) = self.get_or_alloc(size, alloc, &free);
 
-        loop {
-            if old_reader_bitmap == 0 {
-                return;
-            }
-
-            match self.reader_bitmap.compare_exchange_weak(
-                old_reader_bitmap,
-                new_reader_bitmap,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_reader_bitmap = old,
-            };
+        Recycled {
+            ptr,
+            size,
+            list: self,
+            free: Some(free),
         }
+    }
 
-        let mut old_reader_bitmap_copy = old_reader_bitmap;
-
-        loop {
-            let first_set_spot = old_reader_bitmap_copy.trailing_zeros();
+    /// Same as [FreeList::throw] but can also use the slots
+    /// reserved with [FreeList::set_reserved_slots].
+    pub fn throw_priority(&self, ptr: *mut T, size: usize) -> Result<(), Error> {
+        self.throw_into(ptr, size, true)
+    }
 
-            if first_set_spot == usize::BITS {
-                break;
-            }
+    /// Reserves `reserved` slots of the bucket for `size`, so that
+    /// [FreeList::throw] returns BucketFull once only these are left,
+    /// keeping room for [FreeList::throw_priority].
+    ///
+    /// See [Dump::set_reserved_slots].
+    pub fn set_reserved_slots(&self, size: usize, reserved: usize) -> Result<(), Error> {
+        self.buckets[Self::bucket_index(size)?].set_reserved_slots(reserved);
 
-            unset!(in old_reader_bitmap_copy, usize, first_set_spot);
+        Ok(())
+    }
 
-            let dump_ptr = self.dump.get();
-            let val_at_index = unsafe { (*dump_ptr)[first_set_spot as usize] };
+    /// [FreeList::throw], or [FreeList::throw_priority] if `priority` is true.
+    fn throw_into(&self, ptr: *mut T, size: usize, priority: bool) -> Result<(), Error> {
+        self.check_sealed()?;
 
-            f(val_at_index);
+        if ptr.is_null() {
+            return Err(Error::NullPointer);
         }
 
-        let mut old_writer_bitmap = self.writer_bitmap.load(Ordering::Relaxed);
+        let idx = Self::bucket_index(size)?;
 
-        loop {
-            let new_writer_bitmap = old_writer_bitmap & !old_reader_bitmap;
-
-            match self.writer_bitmap.compare_exchange_weak(
-                old_writer_bitmap,
-                new_writer_bitmap,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(old) => old_writer_bitmap = old,
+        Self::check_size(ptr, size)?;
+
+        self.hold_bytes(size)?;
+
+        #[cfg(feature = "canary")]
+        unsafe {
+            (ptr as *mut u8).add(size).cast::<usize>().write_unaligned(CANARY)
+        };
+
+        let dump = &self.buckets[idx];
+        let res = if priority { dump.throw_priority(ptr) } else { dump.throw(ptr) };
+
+        res.map_err(|_| {
+            self.release_bytes(idx, 1);
+
+            // The dump doesn't say why it failed, so it's looked up again
+            #[cfg(feature = "debug-checks")]
+            if dump.holds(ptr) {
+                return Error::DoubleThrow;
+            }
+
+            Error::BucketFull
+        })
+    }
+
+    /// Clears the freelist.
+    ///
+    /// f(ptr, bucket_size)
+    ///   ptr:
+    ///     ptr to free
+    ///   bucket_size:
+    ///     power of 2. For example, if
+    ///     this value is 4, size to free
+    ///     is 16.
+    pub fn clear(&self, f: impl Fn(*mut T, usize)) {
+        for (idx, dump) in self.buckets.iter().enumerate() {
+            dump.clear(|ptr| {
+                self.release_bytes(idx, 1);
+                f(ptr, idx)
+            })
+        }
+    }
+
+    /// Returns the bucket sizes along with the average time the pointers
+    /// recycled from them spent parked. Buckets that nothing was
+    /// recycled from yet are left out.
+    ///
+    /// See [Dump::avg_residency].
+    #[cfg(feature = "stats")]
+    pub fn avg_residency(&self) -> Vec<(usize, Duration)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, dump)| Some((1 << idx, dump.avg_residency()?)))
+            .collect()
+    }
+
+    /// Returns the bucket sizes along with their pressure, which
+    /// helps decide which buckets need more or less capacity.
+    ///
+    /// See [Dump::pressure].
+    #[cfg(feature = "stats")]
+    pub fn pressure(&self) -> Vec<(usize, i64)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(idx, dump)| (1 << idx, dump.pressure()))
+            .collect()
+    }
+
+    /// Same as [FreeList::clear] but the buckets are cleared in
+    /// parallel on the rayon thread pool, as they are independent
+    /// of each other. Helps with tearing down big, full freelists.
+    ///
+    /// `f` is called from multiple threads, hence `Sync`.
+    #[cfg(feature = "parallel-clear")]
+    pub fn clear_parallel(&self, f: impl Fn(*mut T, usize) + Sync) {
+        use rayon::prelude::*;
+
+        self.buckets.par_iter().enumerate().for_each(|(idx, dump)| {
+            dump.clear(|ptr| {
+                self.release_bytes(idx, 1);
+                f(ptr, idx)
+            })
+        })
+    }
+
+    /// Same as [FreeList::recycle] but if the bucket for `size` is empty,
+    /// a block of `2 * size` is recycled and split into two halves.
+    /// The upper half is parked in the bucket for `size`, and the lower
+    /// half is returned along with its size.
+    ///
+    /// If the upper half can't be parked as the bucket filled up in the
+    /// meantime, the block is returned whole, with `2 * size` as its size.
+    ///
+    /// Blocks in the bucket for `2 * size` must really be splittable,
+    /// e.g. not be allocations that must be freed as a whole. With
+    /// `size-checks` feature, both halves are recorded with `size`.
+    ///
+    /// With `canary` feature, the upper half starts `CANARY_PADDING`
+    /// bytes further, so that the lower half has room for its canary.
+    /// Blocks in the bucket for `2 * size` must then be at least
+    /// `2 * (size + CANARY_PADDING)` bytes, like the ones merged by
+    /// [FreeList::try_coalesce] are.
+    pub fn recycle_by_splitting(&self, size: usize) -> Result<(*mut T, usize), Error> {
+        match self.recycle(size) {
+            Err(Error::BucketEmpty) => {}
+            res => return res.map(|ptr| (ptr, size)),
+        }
+
+        let block = self.recycle(size << 1)?;
+        let upper = (block as *mut u8).wrapping_add(size + CANARY_PADDING) as *mut T;
+
+        #[cfg(feature = "size-checks")]
+        {
+            record_allocation_size(block, size);
+            record_allocation_size(upper, size);
+        }
+
+        if self.throw(upper, size).is_ok() {
+            Ok((block, size))
+        } else {
+            #[cfg(feature = "size-checks")]
+            {
+                forget_allocation_size(upper);
+                record_allocation_size(block, size << 1);
+            }
+
+            Ok((block, size << 1))
+        }
+    }
+
+    /// Merges pairs of blocks in the bucket for `size` into blocks of
+    /// the next bucket up, like a buddy allocator would, and returns
+    /// the number of merges.
+    ///
+    /// `is_buddy(a, b)` tells whether two blocks can be merged, e.g.
+    /// they are adjacent and the lower one is aligned to `2 * size`.
+    /// The lower address of the two is thrown into the next bucket.
+    /// If it can't be, the pair is left as it was.
+    ///
+    /// With `canary` feature, every block is followed by its canary,
+    /// so adjacent blocks are `size + CANARY_PADDING` apart. Blocks
+    /// closer than that are never merged, whatever `is_buddy` says.
+    /// With `size-checks` feature, the merged block is recorded
+    /// with `2 * size`.
+    ///
+    /// Returns BucketNotAvailable if there's no bucket above `size`.
+    /// See [Dump::coalesce].
+    pub fn try_coalesce(&self, size: usize, is_buddy: impl Fn(*mut T, *mut T) -> bool) -> Result<usize, Error> {
+        self.check_sealed()?;
+
+        let idx = Self::bucket_index(size)?;
+        Self::bucket_index(size << 1)?;
+
+        let apart = |a: *mut T, b: *mut T| a.max(b) as usize - a.min(b) as usize >= size + CANARY_PADDING;
+
+        Ok(self.buckets[idx].coalesce(|a, b| apart(a, b) && is_buddy(a, b), |a, b| self.merge(a, b, size)))
+    }
+
+    /// Throws the lower of buddies `a` and `b` of `size` as a single
+    /// block of `2 * size`. Returns false if either fails the checks
+    /// of [FreeList::recycle] or the throw fails.
+    fn merge(&self, a: *mut T, b: *mut T, size: usize) -> bool {
+        if Self::check_recycled(a, size).is_err() || Self::check_recycled(b, size).is_err() {
+            return false;
+        }
+
+        let lower = a.min(b);
+        let idx = size.trailing_zeros() as usize;
+
+        #[cfg(feature = "size-checks")]
+        record_allocation_size(lower, size << 1);
+
+        // Both are out of the bucket, and come back as one if the throw works
+        self.release_bytes(idx, 2);
+
+        if self.throw(lower, size << 1).is_err() {
+            #[cfg(feature = "size-checks")]
+            record_allocation_size(lower, size);
+
+            self.bytes_held.fetch_add(size << 1, Ordering::Relaxed);
+
+            return false;
+        }
+
+        // The upper block is a part of the lower one now
+        #[cfg(feature = "size-checks")]
+        forget_allocation_size(a.max(b));
+
+        true
+    }
+
+    /// Same as [FreeList::clear] but within each bucket, `f` is
+    /// called for the pointers in ascending order of address.
+    ///
+    /// See [Dump::clear_sorted].
+    pub fn clear_sorted(&self, f: impl Fn(*mut T, usize)) {
+        for (idx, dump) in self.buckets.iter().enumerate() {
+            dump.clear_sorted(|ptr| {
+                self.release_bytes(idx, 1);
+                f(ptr, idx)
+            })
+        }
+    }
+
+    /// Warms up every bucket with up to `per_bucket` blocks, calling
+    /// `alloc(size)` for each, and returns how many were stored in each
+    /// bucket, smallest bucket first.
+    ///
+    /// The blocks returned by `alloc` must meet the requirements of
+    /// [FreeList::throw]. A bucket stops being filled as soon as `alloc`
+    /// returns null, and never gets more blocks than it has room for.
+    ///
+    /// Meant to be called at startup, before the freelist is in use.
+    /// If a block still can't be thrown, e.g. because of concurrent
+    /// throws or the cap of [FreeList::set_max_bytes], it is passed to
+    /// `free(ptr, size)` and the bucket stops being filled.
+    #[cfg(feature = "alloc")]
+    pub fn prefill_all(
+        &self,
+        per_bucket: usize,
+        mut alloc: impl FnMut(usize) -> *mut T,
+        mut free: impl FnMut(*mut T, usize),
+    ) -> Vec<usize> {
+        (0..N)
+            .map(|idx| {
+                let size = 1 << idx;
+                let dump = &self.buckets[idx];
+                let room = dump.free_slots().saturating_sub(dump.reserved.load(Ordering::Relaxed));
+
+                (0..per_bucket.min(room))
+                    .map(|_| alloc(size))
+                    .take_while(|ptr| !ptr.is_null())
+                    .take_while(|&ptr| match self.throw(ptr, size) {
+                        Ok(()) => true,
+                        Err(_) => {
+                            free(ptr, size);
+                            false
+                        }
+                    })
+                    .count()
+            })
+            .collect()
+    }
+
+    /// Returns a view of this freelist that hands out and takes
+    /// `*mut U` instead of `*mut T`, sharing the same buckets.
+    ///
+    /// Only pointers are stored, and the layout of a freelist doesn't
+    /// depend on `T`, so typed code can work on the same storage as
+    /// untyped code, e.g. the `FreeList<c_void, N>` of `calloc` module.
+    ///
+    /// # Safety
+    ///
+    /// Pointers recycled through the view are only cast, so every
+    /// pointer in the buckets used through it must actually point to
+    /// memory that is valid as a `U`, for as long as it's used as one.
+    pub unsafe fn cast<U>(&self) -> &FreeList<U, N, W, B> {
+        &*(self as *const Self as *const FreeList<U, N, W, B>)
+    }
+
+    /// Clears bucket for the particular size.
+    pub fn clear_bucket(&self, size: usize, f: impl Fn(*mut T)) -> Result<(), Error> {
+        let idx = Self::bucket_index(size)?;
+
+        self.buckets[idx].clear(|ptr| {
+            self.release_bytes(idx, 1);
+            f(ptr)
+        });
+        Ok(())
+    }
+
+    /// Takes all the pointers out of the bucket for `size` at once
+    /// and returns them, so the caller can dispose of them.
+    ///
+    /// The pointers are claimed by a single update of the bucket's
+    /// bitmap, like [FreeList::clear_bucket] does. See [Dump::take].
+    #[cfg(feature = "alloc")]
+    pub fn take_bucket(&self, size: usize) -> Result<Vec<*mut T>, Error> {
+        let idx = Self::bucket_index(size)?;
+        let ptrs = self.buckets[idx].take();
+        self.release_bytes(idx, ptrs.len());
+
+        Ok(ptrs)
+    }
+
+    /// Returns an iterator that takes the pointers out of the bucket
+    /// for `size` one at a time, with [Dump::recycle].
+    ///
+    /// Unlike [FreeList::clear_bucket], the caller decides what to do
+    /// with each pointer and can stop early, leaving the rest parked.
+    /// Pointers thrown while iterating may or may not be yielded.
+    pub fn drain_bucket(&self, size: usize) -> Result<DrainBucket<'_, T, W, B>, Error> {
+        let idx = Self::bucket_index(size)?;
+
+        Ok(DrainBucket {
+            dump: &self.buckets[idx],
+            bytes_held: &self.bytes_held,
+            size: 1 << idx,
+        })
+    }
+
+    /// Rewrites every pointer stored in the freelist.
+    ///
+    /// Meant for allocators that move memory around (e.g. a
+    /// compacting arena) and need to fix up the parked pointers
+    /// in one pass. Needs exclusive access, so no other thread
+    /// can be throwing or recycling meanwhile.
+    ///
+    /// f(ptr, bucket_size) -> new_ptr
+    ///   ptr:
+    ///     currently stored ptr
+    ///   bucket_size:
+    ///     power of 2, same as in [FreeList::clear].
+    ///   new_ptr:
+    ///     ptr to store in place of `ptr`
+    pub fn remap(&mut self, f: impl Fn(*mut T, usize) -> *mut T) {
+        for (idx, dump) in self.buckets.iter_mut().enumerate() {
+            dump.remap(|ptr| f(ptr, idx))
+        }
+    }
+
+    /// Checks that every size in `sizes` can be thrown into or
+    /// recycled from this freelist.
+    ///
+    /// Meant for batch callers to fail fast before operating.
+    /// On failure, returns the index of the first invalid size
+    /// in `sizes` along with the error that any operation with
+    /// it would have returned.
+    pub fn validate_sizes(&self, sizes: &[usize]) -> Result<(), (usize, Error)> {
+        for (idx, &size) in sizes.iter().enumerate() {
+            Self::bucket_index(size).map_err(|err| (idx, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Seals the freelist and clears it.
+    ///
+    /// Once sealed, [FreeList::recycle] and [FreeList::throw] return
+    /// Sealed. This is meant for shutdown, when nothing should be
+    /// parked anymore.
+    ///
+    /// Operations that were already in progress when the freelist got
+    /// sealed may still complete after the clear. So, if the caller
+    /// can't rule those out, it should clear again once they're done.
+    ///
+    /// See [FreeList::clear] for `f`.
+    pub fn drain_and_seal(&self, f: impl Fn(*mut T, usize)) {
+        self.sealed.store(true, Ordering::Relaxed);
+        self.clear(f);
+    }
+
+    /// Returns true if [FreeList::drain_and_seal] has been called.
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::Relaxed)
+    }
+
+    fn check_sealed(&self) -> Result<(), Error> {
+        if self.is_sealed() {
+            Err(Error::Sealed)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns how many more pointers can be thrown into the
+    /// freelist, summed over all the buckets.
+    ///
+    /// See [Dump::free_slots].
+    pub fn free_slots(&self) -> usize {
+        self.buckets.iter().map(Dump::free_slots).sum()
+    }
+
+    /// Returns the number of pointers that can be recycled
+    /// from the freelist, summed over all the buckets.
+    ///
+    /// Under concurrent use, buckets may change while they are being
+    /// summed up, so this is only a snapshot. See [Dump::len].
+    pub fn len(&self) -> usize {
+        self.len_with(Ordering::Relaxed)
+    }
+
+    /// Same as [FreeList::len] but the bitmaps are loaded with `ordering`.
+    ///
+    /// See [Dump::len_with] for which ordering to pick.
+    pub fn len_with(&self, ordering: Ordering) -> usize {
+        self.buckets.iter().map(|dump| dump.len_with(ordering)).sum()
+    }
+
+    /// Returns true if nothing can be recycled from any bucket.
+    ///
+    /// Stops at the first bucket that isn't empty. See [Dump::is_empty].
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(Dump::is_empty)
+    }
+
+    /// Returns true if nothing more can be thrown into the bucket
+    /// for `size`, so producers can skip trying.
+    ///
+    /// See [Dump::is_full].
+    pub fn is_bucket_full(&self, size: usize) -> Result<bool, Error> {
+        Ok(self.buckets[Self::bucket_index(size)?].is_full())
+    }
+
+    /// Returns the number of pointers that can be recycled
+    /// from the bucket for `size`.
+    ///
+    /// See [Dump::len].
+    pub fn bucket_len(&self, size: usize) -> Result<usize, Error> {
+        Ok(self.buckets[Self::bucket_index(size)?].len())
+    }
+
+    /// Returns the number of bytes parked in the bucket for
+    /// `size`, i.e., [FreeList::bucket_len] times `size`.
+    pub fn bytes_held_in_bucket(&self, size: usize) -> Result<usize, Error> {
+        Ok(self.bucket_len(size)? * size)
+    }
+
+    /// Caps the bytes the freelist holds at `max_bytes`, as counted
+    /// by [FreeList::total_bytes_held]. Throws that would go past it
+    /// return BudgetExceeded, so that the caller frees the block
+    /// instead. Blocks already held stay, even if `max_bytes` is lower.
+    ///
+    /// A throw counts its block before storing it, so concurrent throws
+    /// can't go past the cap together, though one may be turned down
+    /// for the bytes of another that fails, e.g. as its bucket is full.
+    /// By default there's no cap, i.e., it's `usize::MAX`.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the cap set by [FreeList::set_max_bytes].
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Adds a block of `size` to [FreeList::total_bytes_held] before
+    /// it's stored. Returns BudgetExceeded, leaving the count as it
+    /// was, if that would take the freelist past [FreeList::max_bytes].
+    fn hold_bytes(&self, size: usize) -> Result<(), Error> {
+        let held = self.bytes_held.fetch_add(size, Ordering::Relaxed);
+
+        if held.saturating_add(size) > self.max_bytes() {
+            self.bytes_held.fetch_sub(size, Ordering::Relaxed);
+            Err(Error::BudgetExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the number of bytes parked in the freelist.
+    ///
+    /// This is a counter kept up to date by the throws and the
+    /// recycles, so unlike [FreeList::len], the buckets aren't
+    /// summed up. Blocks are counted with their bucket size, which
+    /// is all the freelist knows, even if they were allocated bigger.
+    pub fn total_bytes_held(&self) -> usize {
+        self.bytes_held.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many more pointers can be thrown into
+    /// the bucket for `size`.
+    ///
+    /// See [Dump::free_slots].
+    pub fn free_slots_for(&self, size: usize) -> Result<usize, Error> {
+        Ok(self.buckets[Self::bucket_index(size)?].free_slots())
+    }
+
+    /// Returns the snapshots of reader and writer bitmaps, in
+    /// that order, of the bucket for `size`.
+    ///
+    /// See [Dump::reader_bitmap_snapshot] and
+    /// [Dump::writer_bitmap_snapshot] for the caveats.
+    pub fn bucket_bitmaps(&self, size: usize) -> Result<(B, B), Error> {
+        let dump = &self.buckets[Self::bucket_index(size)?];
+
+        Ok((dump.reader_bitmap_snapshot(), dump.writer_bitmap_snapshot()))
+    }
+
+    /// Calls `f` once per bucket with the pointers that can currently
+    /// be recycled from it, without clearing anything.
+    ///
+    /// f(size, ptrs)
+    ///   size:
+    ///     size of the bucket
+    ///   ptrs:
+    ///     pointers parked in the bucket
+    ///
+    /// Each bucket is consistent on its own, but buckets are read one
+    /// after the other. Meant for dumping the state, e.g., in a crash handler.
+    ///
+    /// # Safety
+    ///
+    /// No recycle or clear may run on this freelist concurrently.
+    /// See [Dump::snapshot].
+    pub unsafe fn snapshot_all(&self, f: impl Fn(usize, &[*mut T])) {
+        for (i, dump) in self.buckets.iter().enumerate() {
+            dump.snapshot(|ptrs| f(1 << i, ptrs));
+        }
+    }
+
+    /// Returns the size of the smallest bucket that fits `size`
+    /// bytes, i.e., `size` rounded up to the next power of 2.
+    /// Returns None if `size` exceeds [FreeList::MAX_SIZE].
+    ///
+    /// Nothing is thrown or recycled, this is just routing logic
+    /// for allocators to decide where a request should go.
+    pub fn fitting_bucket(&self, size: usize) -> Option<usize> {
+        if size > Self::MAX_SIZE {
+            None
+        } else {
+            Some(size.next_power_of_two())
+        }
+    }
+
+    /// Checks `ptr` which was just taken out of the bucket for `size`.
+    /// See [FreeList::recycle].
+    #[cfg_attr(not(feature = "canary"), allow(unused_variables))]
+    fn check_recycled(ptr: *mut T, size: usize) -> Result<*mut T, Error> {
+        Self::check_size(ptr, size)?;
+
+        #[cfg(feature = "canary")]
+        if unsafe { (ptr as *mut u8).add(size).cast::<usize>().read_unaligned() } != CANARY {
+            return Err(Error::Corrupted(ptr as usize));
+        }
+
+        Ok(ptr)
+    }
+
+    /// With `size-checks` feature, checks that `size` matches
+    /// the one recorded by [record_allocation_size] for `ptr`.
+    #[cfg_attr(not(feature = "size-checks"), allow(unused_variables))]
+    fn check_size(ptr: *mut T, size: usize) -> Result<(), Error> {
+        #[cfg(feature = "size-checks")]
+        match ALLOCATION_SIZES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(ptr as usize))
+        {
+            Some(&recorded) if recorded != size => return Err(Error::SizeMismatch(ptr as usize)),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Returns index of the bucket for `size`.
+    ///
+    /// Returns SizeNotPowerOf2 if `size` is not power of 2
+    /// Returns BucketNotAvailable is bucket for the given
+    /// size doesn't exist.
+    fn bucket_index(size: usize) -> Result<usize, Error> {
+        if !size.is_power_of_two() {
+            return Err(Error::SizeNotPowerOf2);
+        }
+
+        let power = size.trailing_zeros();
+
+        if power < N as u32 {
+            Ok(power as usize)
+        } else {
+            Err(Error::BucketNotAvailable)
+        }
+    }
+}
+
+impl<const N: usize, const W: usize, B: BitmapInt> FreeList<u8, N, W, B> {
+    /// Same as [FreeList::recycle] but also returns the size of the
+    /// block in bytes, for using it right away as an uninitialized
+    /// buffer, e.g. to `read(2)` into.
+    ///
+    /// The contents of the block are whatever its previous user left
+    /// in it, so they must be treated as uninitialized. Nothing is
+    /// zeroed, which is the point. Once done, the block can be thrown
+    /// back with the returned size.
+    pub fn recycle_uninit(&self, size: usize) -> Result<(*mut u8, usize), Error> {
+        self.recycle(size).map(|ptr| (ptr, size))
+    }
+}
+
+/// Remembers the bucket a pointer was taken out of by
+/// [FreeList::recycle_checked_out], so that
+/// [FreeList::throw_checked_in] can put it back there.
+///
+/// It's just the power of 2 of the bucket, so it costs
+/// nothing at runtime. It isn't `Clone` as there's one
+/// token for every pointer that is checked out.
+#[must_use = "the token is needed to throw the pointer back into its bucket"]
+#[derive(Debug)]
+pub struct ThrowToken {
+    power: u32,
+}
+
+impl ThrowToken {
+    /// Returns the size of the bucket this token is for.
+    pub fn size(&self) -> usize {
+        1 << self.power
+    }
+}
+
+/// Prints the occupancy of every bucket, e.g.
+/// `FreeList {bucket[1B]: 3/64, bucket[2B]: 0/64, ...}`.
+/// The pointers themselves aren't printed.
+impl<T, const N: usize, const W: usize, B: BitmapInt> fmt::Debug for FreeList<T, N, W, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buckets = self.buckets.iter().enumerate().map(|(idx, dump)| {
+            let occupancy = Occupancy {
+                len: dump.len_with(Ordering::Acquire),
+                capacity: W,
             };
+
+            (BucketName(1 << idx), occupancy)
+        });
+
+        f.write_str("FreeList ")?;
+        f.debug_map().entries(buckets).finish()
+    }
+}
+
+/// Key of a bucket in the Debug output of [FreeList].
+struct BucketName(usize);
+
+impl fmt::Debug for BucketName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bucket[{}B]", self.0)
+    }
+}
+
+/// Value of a bucket in the Debug output of [FreeList].
+struct Occupancy {
+    len: usize,
+    capacity: usize,
+}
+
+impl fmt::Debug for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.len, self.capacity)
+    }
+}
+
+/// Iterator over the pointers taken out of a bucket,
+/// returned by [FreeList::drain_bucket].
+pub struct DrainBucket<'a, T, const W: usize = { usize::BITS as usize }, B: BitmapInt = usize> {
+    dump: &'a Dump<T, W, B>,
+    /// See [FreeList::total_bytes_held]
+    bytes_held: &'a AtomicUsize,
+    size: usize,
+}
+
+impl<'a, T, const W: usize, B: BitmapInt> Iterator for DrainBucket<'a, T, W, B> {
+    type Item = *mut T;
+
+    fn next(&mut self) -> Option<*mut T> {
+        let ptr = self.dump.recycle()?;
+        self.bytes_held.fetch_sub(self.size, Ordering::Relaxed);
+
+        Some(ptr)
+    }
+}
+
+/// A block returned by [FreeList::checkout]. Dropping it
+/// throws the block back, or frees it if that fails.
+pub struct Recycled<'a, T, F: FnOnce(*mut T), const N: usize, const W: usize = { usize::BITS as usize }, B: BitmapInt = usize> {
+    ptr: *mut T,
+    size: usize,
+    list: &'a FreeList<T, N, W, B>,
+    /// Only taken on drop
+    free: Option<F>,
+}
+
+impl<'a, T, F: FnOnce(*mut T), const N: usize, const W: usize, B: BitmapInt> Recycled<'a, T, F, N, W, B> {
+    /// Returns the block.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Returns the actual size of the block, see [FreeList::get_or_alloc].
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the block and its size without giving it back,
+    /// so that the caller becomes responsible for it.
+    pub fn into_raw(mut self) -> (*mut T, usize) {
+        self.free = None;
+
+        (self.ptr, self.size)
+    }
+}
+
+impl<'a, T, F: FnOnce(*mut T), const N: usize, const W: usize, B: BitmapInt> Drop for Recycled<'a, T, F, N, W, B> {
+    fn drop(&mut self) {
+        if let Some(free) = self.free.take() {
+            self.list.put_or_free(self.ptr, self.size, free);
         }
     }
 }
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+/// Error thrown by methods of FreeList
+pub enum Error {
+    /// The bucket for the requested size is full.
+    /// Memory can't be stored on the freelist.
+    BucketFull,
+    /// Bucket for the given size is not available.
+    /// Can't be stored or fetched from the freelist.
+    BucketNotAvailable,
+    /// The bucket for the requested size is empty.
+    /// Basically, memory can't be obtained from freelist.
+    BucketEmpty,
+    /// The freelist only supports power of 2
+    /// sizes. The calling code needs to handle
+    /// going to next power of 2 if needed.
+    SizeNotPowerOf2,
+    /// The canary past the recycled block was overwritten.
+    /// Only returned with `canary` feature.
+    ///
+    /// Holds the address of the block, which has been taken out
+    /// of the freelist, so that the caller can free it.
+    Corrupted(usize),
+    /// The freelist has been sealed with [FreeList::drain_and_seal].
+    /// Nothing can be stored or fetched from it anymore.
+    Sealed,
+    /// The size doesn't match the one recorded for the pointer
+    /// at allocation. Only returned with `size-checks` feature.
+    ///
+    /// Holds the address of the block. If it was being recycled, it
+    /// has been taken out of the freelist, so the caller can free it.
+    SizeMismatch(usize),
+    /// Other threads were using the bucket at the same time. Only
+    /// returned by the variants that don't retry, see [RecycleError].
+    Contended,
+    /// A size of 0 was given where a block size is needed.
+    ZeroSize,
+    /// A null pointer was thrown. It would be handed back out
+    /// by a later recycle, so it's rejected right away.
+    NullPointer,
+    /// The pointer is already in the bucket, so throwing it again
+    /// would hand it out twice. Only returned with `debug-checks` feature.
+    DoubleThrow,
+    /// Holding the block would take the freelist past the
+    /// bytes it may hold, see [FreeList::set_max_bytes].
+    BudgetExceeded,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BucketFull => write!(f, "bucket is full"),
+            Error::BucketNotAvailable => write!(f, "bucket not available"),
+            Error::BucketEmpty => write!(f, "bucket is empty"),
+            Error::SizeNotPowerOf2 => write!(f, "given size should be power of 2"),
+            Error::Corrupted(addr) => write!(f, "canary past the block at {:#x} was overwritten", addr),
+            Error::Sealed => write!(f, "freelist is sealed"),
+            Error::SizeMismatch(addr) => write!(f, "size doesn't match the one recorded for {:#x}", addr),
+            Error::Contended => write!(f, "bucket is being used by other threads"),
+            Error::ZeroSize => write!(f, "size should not be 0"),
+            Error::NullPointer => write!(f, "pointer is null"),
+            Error::DoubleThrow => write!(f, "pointer is already in the bucket"),
+            Error::BudgetExceeded => write!(f, "freelist would go past its byte budget"),
+        }
+    }
+}
+
+/// Why a recycle that doesn't retry, like [Dump::try_recycle_once],
+/// came back empty handed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecycleError {
+    /// There was nothing to recycle.
+    Empty,
+    /// Other threads were using the dump at the same time, so
+    /// there may still be something to recycle on a retry.
+    Contended,
+}
+
+// The only part of `Error` that needs std, `Display` above is core only
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+// Without std, the same trait from core, which is the one std re-exports
+// on the toolchains that have it. So only one of these impls can exist.
+#[cfg(all(feature = "core-error", not(feature = "std")))]
+#[allow(clippy::incompatible_msrv)]
+impl core::error::Error for Error {}
+
+/// In this struct,
+/// max_bits!(reader_bitmap) == max_bits!(writer_bitmap) == dump.len()
+///
+/// The accesses to dump[] array are synchronized by reader_bitmap
+/// and writer_bitmap.
+///
+/// The length is `W`, which is (sizeof(usize) * 8) by default and
+/// can't be more than the bits of `B`, as each slot is a bit of the
+/// bitmaps. E.g., `Dump<T, 64, u64>` holds 64 values on any target.
+/// That is actually all what is needed as such a structure is
+/// meant for cases where producer and consumer are equally fast.
+/// Otherwise also, it isn't generally required to keep a lot
+/// of memory unfreed. A smaller `W` makes the dump smaller.
+///
+/// With `mutex-buckets` feature, every operation takes a spin lock
+/// and the bitmaps are updated with plain loads and stores instead
+/// of compare-and-swap loops. This can be faster on targets where
+/// atomic read-modify-write operations are emulated.
+// `repr(C)` keeps the layout the same for all `T`, see `FreeList::cast()`
+#[repr(C)]
+pub struct Dump<T, const W: usize = { usize::BITS as usize }, B: BitmapInt = usize> {
+    // Consumers mostly touch `reader_bitmap` and producers mostly touch
+    // `writer_bitmap`. Each gets its own cache line so that they don't
+    // keep invalidating each other's line, i.e., no false sharing.
+    reader_bitmap: CachePadded<AtomicBitmap<B>>,
+    writer_bitmap: CachePadded<AtomicBitmap<B>>,
+    /// Number of slots only [Dump::throw_priority] can use
+    reserved: AtomicUsize,
+    dump: [UnsafeCell<*mut T>; W],
+    #[cfg(feature = "mutex-buckets")]
+    lock: spin::Mutex<()>,
+    /// When the value at each index was thrown, see [nanos_since_start]
+    #[cfg(feature = "stats")]
+    thrown_at: [AtomicU64; W],
+    /// Total time recycled values spent in the dump, in nanoseconds
+    #[cfg(feature = "stats")]
+    residency_nanos: AtomicU64,
+    /// Number of values that `residency_nanos` adds up
+    #[cfg(feature = "stats")]
+    residency_count: AtomicU64,
+    /// Recycles that got nothing minus throws that found it full
+    #[cfg(feature = "stats")]
+    pressure: AtomicI64,
+    /// Values recycled, see [Dump::counters]
+    #[cfg(feature = "stats")]
+    hits: AtomicUsize,
+    /// Recycles that got nothing
+    #[cfg(feature = "stats")]
+    misses: AtomicUsize,
+    /// Throws that stored the value
+    #[cfg(feature = "stats")]
+    throws: AtomicUsize,
+    /// Throws that found the dump full
+    #[cfg(feature = "stats")]
+    full_throws: AtomicUsize,
+    /// Failed compare-and-swaps on the bitmaps that had to be retried
+    #[cfg(feature = "stats")]
+    cas_retries: AtomicUsize,
+}
+
+unsafe impl<T, const W: usize, B: BitmapInt> Send for Dump<T, W, B> {}
+unsafe impl<T, const W: usize, B: BitmapInt> Sync for Dump<T, W, B> {}
+
+/// Prints the number of bits set in each bitmap, i.e., the values
+/// that can be recycled and the spots that are taken. The values
+/// themselves aren't printed.
+impl<T, const W: usize, B: BitmapInt> fmt::Debug for Dump<T, W, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dump")
+            .field("reader_bits", &self.len_with(Ordering::Acquire))
+            .field("writer_bits", &self.writer_bitmap_snapshot().count_ones())
+            .finish()
+    }
+}
+
+impl<T, const W: usize, B: BitmapInt> Default for Dump<T, W, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const W: usize, B: BitmapInt> Dump<T, W, B> {
+    const_fn! {
+        /// Returns a new Dump instance.
+        ///
+        /// ```ignore
+        ///
+        /// struct Example {
+        ///     a: i32,
+        ///     b: String,
+        /// }
+        ///
+        /// let dump = Dump::<Example>::new();
+        /// ```
+        ///
+        /// `W` can't be more than the bits of `B`.
+        pub fn new() -> Self {
+            assert!(W <= B::BITS as usize, "slots should fit in a bitmap");
+
+            Dump {
+                reader_bitmap: CachePadded(AtomicBitmap::new()),
+                writer_bitmap: CachePadded(AtomicBitmap::new()),
+                reserved: AtomicUsize::new(0),
+                #[cfg(not(loom))]
+                dump: [Self::EMPTY_SPOT; W],
+                // loom itself needs a newer compiler than the MSRV
+                #[cfg(loom)]
+                #[allow(clippy::incompatible_msrv)]
+                dump: core::array::from_fn(|_| UnsafeCell::new(null_mut())),
+                #[cfg(feature = "mutex-buckets")]
+                lock: spin::Mutex::new(()),
+                #[cfg(feature = "stats")]
+                thrown_at: [Self::NEVER_THROWN; W],
+                #[cfg(feature = "stats")]
+                residency_nanos: AtomicU64::new(0),
+                #[cfg(feature = "stats")]
+                residency_count: AtomicU64::new(0),
+                #[cfg(feature = "stats")]
+                pressure: AtomicI64::new(0),
+                #[cfg(feature = "stats")]
+                hits: AtomicUsize::new(0),
+                #[cfg(feature = "stats")]
+                misses: AtomicUsize::new(0),
+                #[cfg(feature = "stats")]
+                throws: AtomicUsize::new(0),
+                #[cfg(feature = "stats")]
+                full_throws: AtomicUsize::new(0),
+                #[cfg(feature = "stats")]
+                cas_retries: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    // Only used as operand of the array repeat expression in `new()`
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SPOT: UnsafeCell<*mut T> = UnsafeCell::new(null_mut());
+
+    // Only used as operand of the array repeat expression in `new()`
+    #[cfg(feature = "stats")]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NEVER_THROWN: AtomicU64 = AtomicU64::new(0);
+
+    /// Returns the number of values a dump can hold, i.e., `W`.
+    pub const fn capacity() -> usize {
+        W
+    }
+
+    /// Serializes the operations on this dump with `mutex-buckets`
+    /// feature. Otherwise, this is a no-op.
+    #[cfg(feature = "mutex-buckets")]
+    fn lock(&self) -> spin::MutexGuard<'_, ()> {
+        self.lock.lock()
+    }
+
+    #[cfg(not(feature = "mutex-buckets"))]
+    fn lock(&self) -> NoLock {
+        NoLock
+    }
+
+    /// Same as [Dump::lock] but returns None instead of
+    /// spinning if the lock is already held.
+    #[cfg(feature = "mutex-buckets")]
+    fn try_lock(&self) -> Option<spin::MutexGuard<'_, ()>> {
+        self.lock.try_lock()
+    }
+
+    #[cfg(not(feature = "mutex-buckets"))]
+    fn try_lock(&self) -> Option<NoLock> {
+        Some(NoLock)
+    }
+
+    /// Adds a new element to the dump. On success it returns
+    /// () and on failure returns back the ptr indicating
+    /// that it couldn't be stored.
+    ///
+    /// To synchronize this addition to the dump[] array, the following
+    /// procedure is followed:
+    ///
+    /// 1) It checks `writer_bitmap` for unset bits (0 bits).
+    /// 2) When it finds one, it atomically sets it.
+    /// 3) We use this bit position as the index in `dump[]` to store the value.
+    /// 4) Setting the bit in `writer_bitmap` ensures that no
+    ///    other thread will write at that index.
+    /// 5) After storing `raw` in the `dump[]`, we tell reader threads
+    ///    that this index is available for read. To do this, we set this
+    ///    same bit position in `reader_bitmap` atomically.
+    ///
+    /// Fails if only the slots reserved with [Dump::set_reserved_slots]
+    /// are left. Also fails if `raw` is null, which couldn't be told
+    /// apart from an empty slot when it's recycled later.
+    ///
+    /// With `debug-checks` feature, also fails if `raw` is already in
+    /// the dump. This scans all the occupied slots on every throw.
+    pub fn throw(&self, raw: *mut T) -> Result<(), *mut T> {
+        let reserved = self.reserved.load(Ordering::Relaxed);
+
+        self.throw_within(raw, W.saturating_sub(reserved) as u32)
+    }
+
+    /// Same as [Dump::throw] but can also use the reserved slots.
+    pub fn throw_priority(&self, raw: *mut T) -> Result<(), *mut T> {
+        self.throw_within(raw, W as u32)
+    }
+
+    /// Reserves `reserved` slots for [Dump::throw_priority], so that
+    /// [Dump::throw] fails once the dump holds `capacity - reserved`
+    /// values. This way, a latency critical producer always has room.
+    ///
+    /// Slots aren't reserved at fixed indices, it's only the count of
+    /// occupied slots that is checked. Values already in the dump stay.
+    pub fn set_reserved_slots(&self, reserved: usize) {
+        self.reserved.store(reserved, Ordering::Relaxed);
+    }
+
+    /// [Dump::throw] but fails if `capacity` slots are already occupied.
+    fn throw_within(&self, raw: *mut T, capacity: u32) -> Result<(), *mut T> {
+        if raw.is_null() {
+            return Err(raw);
+        }
+
+        #[cfg(feature = "debug-checks")]
+        if self.holds(raw) {
+            return Err(raw);
+        }
+
+        let _guard = self.lock();
+
+        // occupy the first empty spot, i.e., the first bit which is 0 in `writer_bitmap`.
+        // Acquire pairs with the Release that freed the spot, so that the
+        // last read of `dump[]` at this spot happens before our write.
+        let old_writer_bitmap = self.update_bitmap(&self.writer_bitmap, Ordering::Acquire, |old_writer_bitmap| {
+            let first_empty_spot = old_writer_bitmap.trailing_ones();
+
+            if first_empty_spot as usize >= W || old_writer_bitmap.count_ones() >= capacity {
+                None
+            } else {
+                Some(old_writer_bitmap | B::bit(first_empty_spot))
+            }
+        });
+
+        let first_empty_spot = match old_writer_bitmap {
+            Some(old_writer_bitmap) => old_writer_bitmap.trailing_ones(),
+            None => {
+                #[cfg(feature = "stats")]
+                {
+                    self.pressure.fetch_sub(1, Ordering::Relaxed);
+                    self.full_throws.fetch_add(1, Ordering::Relaxed);
+                }
+
+                return Err(raw);
+            }
+        };
+
+        #[cfg(feature = "stats")]
+        self.throws.fetch_add(1, Ordering::Relaxed);
+
+        self.write_spot(first_empty_spot, raw);
+
+        // Published along with `raw` by the `reader_bitmap` update below
+        #[cfg(feature = "stats")]
+        self.thrown_at[first_empty_spot as usize].store(nanos_since_start(), Ordering::Relaxed);
+
+        /*
+         * Memory order should be `Ordering::Release`.
+         * If it was Ordering::Relaxed, it would become possible
+         * that `recycle()` sees this bit as set in `reader_bitmap`
+         * but doesn't see the newly updated value in `dump[]`.
+         *
+         * The spot's bit in `writer_bitmap` can't have been unset since
+         * we set it, as `clear()` only releases the spots it took out of
+         * `reader_bitmap`, and this one isn't there until right below.
+         *
+         * As the spot is ours, no other thread sets its bit in
+         * `reader_bitmap`, and the others only unset other bits.
+         * So a single `fetch_or` does the same as a CAS loop.
+         */
+        set_bits(&self.reader_bitmap, B::bit(first_empty_spot), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Gets a value from the dump. On success it returns
+    /// the value `*mut T` and on failure (). Failure indicates
+    /// that dump is empty.
+    ///
+    /// To synchronize the retreival from the dump[] array, the following
+    /// procedure is followed:
+    ///
+    /// 1) A set bit is searched in `reader_bitmap` and then we
+    ///    atomically unset that bit in `reader_bitmap`.
+    /// 2) Corresponding to the bit posn that we unset, we get the
+    ///    `dump[bit_posn]`.
+    /// 3) Then to allow writers to use this position for new writes,
+    ///    we unset this bit from `writer_bitmap`.
+    /// 4) Finally, we return `dump[bit_posn]`.
+    pub fn recycle(&self) -> Option<*mut T> {
+        self.recycle_if(|_| true).map(|(ptr, _)| ptr)
+    }
+
+    /// Same as [Dump::recycle] but also returns the index of the
+    /// slot the value came from, i.e., the bit that was unset in
+    /// `reader_bitmap`.
+    ///
+    /// This is mainly for experiments, like correlating the reuse
+    /// pattern of slots with cache behavior.
+    pub fn recycle_with_slot(&self) -> Option<(*mut T, u32)> {
+        self.recycle_if(|_| true)
+    }
+
+    /// Same as [Dump::recycle] but only gets a value if the dump
+    /// holds more than `min_keep` values. Otherwise returns None,
+    /// leaving the values there.
+    ///
+    /// The count is checked against the same `reader_bitmap` that
+    /// the value is taken from, so `min_keep` values are always left.
+    /// Values that are still being thrown aren't counted though.
+    pub fn recycle_if_above(&self, min_keep: usize) -> Option<*mut T> {
+        self.recycle_if(|reader_bitmap| reader_bitmap.count_ones() as usize > min_keep)
+            .map(|(ptr, _)| ptr)
+    }
+
+    /// Same as [Dump::recycle] but only gets a value for which `pred`
+    /// is true. Returns None if there's no such value.
+    ///
+    /// Each candidate is claimed on its own before `pred` is called
+    /// for it, so the value checked is the value returned. Values that
+    /// `pred` rejects are published again in the same spots, as those
+    /// are still ours. So, rejecting a value can never lose it.
+    ///
+    /// With `mutex-buckets` feature, `pred` runs under the bucket lock.
+    pub fn recycle_matching(&self, pred: impl Fn(*mut T) -> bool) -> Option<*mut T> {
+        let _guard = self.lock();
+
+        let mut candidates = self.reader_bitmap.load(Ordering::Acquire);
+
+        loop {
+            let spot = candidates.trailing_zeros();
+
+            if spot == B::BITS {
+                self.count_recycles(0);
+                return None;
+            }
+
+            candidates &= !B::bit(spot);
+
+            let spot_bit = B::bit(spot);
+
+            let claimed = self.update_bitmap(&self.reader_bitmap, Ordering::Acquire, |old_reader_bitmap| {
+                if old_reader_bitmap & spot_bit != B::ZERO {
+                    Some(old_reader_bitmap & !spot_bit)
+                } else {
+                    None
+                }
+            });
+
+            // Some other thread may have taken it in the meantime
+            if claimed.is_none() {
+                continue;
+            }
+
+            if pred(self.read_spot(spot)) {
+                return Some(self.take_spot(spot));
+            }
+
+            // The spot is still ours, so it's published as it is
+            set_bits(&self.reader_bitmap, spot_bit, Ordering::Release);
+        }
+    }
+
+    /// [Dump::recycle_with_slot] but gives up if `cond(reader_bitmap)` is false.
+    fn recycle_if(&self, cond: impl Fn(B) -> bool) -> Option<(*mut T, u32)> {
+        let _guard = self.lock();
+
+        /*
+         * Occupy the first set spot, i.e., the first bit which is 1 in `reader_bitmap`.
+         *
+         * Memory order on success should be `Ordering::Acquire` to pair
+         * with the `Ordering::Release` in `throw()`. Otherwise, the bit
+         * could be seen as set while `dump[]` still holds an old value.
+         */
+        let old_reader_bitmap = self.update_bitmap(&self.reader_bitmap, Ordering::Acquire, |old_reader_bitmap| {
+            if cond(old_reader_bitmap) {
+                unset_first_set_spot(old_reader_bitmap)
+            } else {
+                None
+            }
+        });
+
+        let old_reader_bitmap = match old_reader_bitmap {
+            Some(old_reader_bitmap) => old_reader_bitmap,
+            None => {
+                self.count_recycles(0);
+                return None;
+            }
+        };
+
+        let first_set_spot = old_reader_bitmap.trailing_zeros();
+
+        Some((self.take_spot(first_set_spot), first_set_spot))
+    }
+
+    /// Same as [Dump::recycle] but makes a single attempt to get a
+    /// value. If another thread changes `reader_bitmap` in the meantime,
+    /// this returns Contended right away instead of retrying, even
+    /// though the dump may not be empty. Returns Empty if it is.
+    ///
+    /// This always finishes in a bounded number of steps, trading
+    /// hit rate for predictable latency. With `mutex-buckets` feature,
+    /// it also returns Contended if the bucket lock is held.
+    pub fn try_recycle_once(&self) -> Result<*mut T, RecycleError> {
+        let _guard = self.try_lock().ok_or(RecycleError::Contended)?;
+
+        // Same ordering as in `recycle_if()`
+        let old_reader_bitmap = match update_once(&self.reader_bitmap, Ordering::Acquire, unset_first_set_spot) {
+            Some(res) => res.map_err(|_| RecycleError::Contended)?,
+            None => {
+                self.count_recycles(0);
+                return Err(RecycleError::Empty);
+            }
+        };
+
+        Ok(self.take_spot(old_reader_bitmap.trailing_zeros()))
+    }
+
+    /// Gets up to `want` values from the dump into `local` in one
+    /// go and returns how many were added.
+    ///
+    /// Unlike calling [Dump::recycle] `want` times, all the spots
+    /// are claimed with a single update of `reader_bitmap`, and freed
+    /// with a single update of `writer_bitmap`.
+    #[cfg(feature = "alloc")]
+    pub fn recycle_into(&self, local: &mut Vec<*mut T>, want: usize) -> usize {
+        let _guard = self.lock();
+
+        let mut spots = B::ZERO;
+
+        // occupy up to `want` set spots, starting from the lowest ones,
+        // with the same ordering as in `recycle_if()`
+        self.update_bitmap(&self.reader_bitmap, Ordering::Acquire, |old_reader_bitmap| {
+            let mut new_reader_bitmap = old_reader_bitmap;
+
+            for _ in 0..want {
+                new_reader_bitmap = match unset_first_set_spot(new_reader_bitmap) {
+                    Some(new_reader_bitmap) => new_reader_bitmap,
+                    None => break,
+                };
+            }
+
+            spots = old_reader_bitmap & !new_reader_bitmap;
+
+            if spots == B::ZERO {
+                None
+            } else {
+                Some(new_reader_bitmap)
+            }
+        });
+
+        let mut spots_copy = spots;
+
+        loop {
+            let first_set_spot = spots_copy.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            spots_copy &= !B::bit(first_set_spot);
+
+            local.push(self.read_spot(first_set_spot));
+
+            #[cfg(feature = "stats")]
+            self.record_residency(first_set_spot);
+        }
+
+        // Same as in `recycle_if()`, the spots stay ours until this
+        unset_bits(&self.writer_bitmap, spots, Ordering::Release);
+
+        if want > 0 {
+            self.count_recycles(spots.count_ones() as usize);
+        }
+
+        spots.count_ones() as usize
+    }
+
+    /// Whether `raw` is in one of the spots set in `reader_bitmap`.
+    ///
+    /// The spots aren't claimed, so a concurrent recycle may take one
+    /// while it's being read. That's fine for catching a thread that
+    /// throws the same pointer twice, which is all this is for.
+    #[cfg(feature = "debug-checks")]
+    fn holds(&self, raw: *mut T) -> bool {
+        let mut reader_bitmap = self.reader_bitmap.load(Ordering::Acquire);
+
+        loop {
+            let first_set_spot = reader_bitmap.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                return false;
+            }
+
+            reader_bitmap &= !B::bit(first_set_spot);
+
+            if self.read_spot(first_set_spot) == raw {
+                return true;
+            }
+        }
+    }
+
+    /// Reads `dump[spot]`. The caller must own `spot`, i.e., have it
+    /// set in `writer_bitmap` and unset in `reader_bitmap`, or else
+    /// have it set in `reader_bitmap` with no recycle running.
+    fn read_spot(&self, spot: u32) -> *mut T {
+        self.dump[spot as usize].with(|ptr| unsafe { *ptr })
+    }
+
+    /// Writes `dump[spot]`. The caller must own `spot` as for `read_spot()`.
+    fn write_spot(&self, spot: u32, raw: *mut T) {
+        self.dump[spot as usize].with_mut(|ptr| unsafe { *ptr = raw })
+    }
+
+    /// Reads the value at `first_set_spot`, which the caller just
+    /// unset in `reader_bitmap`, and frees the spot for writers.
+    fn take_spot(&self, first_set_spot: u32) -> *mut T {
+        let retval = self.read_spot(first_set_spot);
+
+        #[cfg(feature = "stats")]
+        self.record_residency(first_set_spot);
+
+        self.count_recycles(1);
+
+        /*
+         * This can't be merged into the `reader_bitmap` update above, as
+         * the bit in `writer_bitmap` must stay set until `dump[]` is read.
+         * Otherwise, a thrower could overwrite the value before it is read.
+         * But only we can unset this bit, so no loop is needed for it.
+         *
+         * Memory order should be `Ordering::Release`, so that a thrower
+         * that gets this spot next writes `dump[]` only after we read it.
+         */
+        unset_bits(&self.writer_bitmap, B::bit(first_set_spot), Ordering::Release);
+
+        retval
+    }
+
+    /// Adds the time the value at `spot` spent in the dump to the
+    /// residency stats. Must be called before the spot is released.
+    #[cfg(feature = "stats")]
+    fn record_residency(&self, spot: u32) {
+        let thrown_at = self.thrown_at[spot as usize].load(Ordering::Relaxed);
+
+        self.residency_nanos
+            .fetch_add(nanos_since_start().saturating_sub(thrown_at), Ordering::Relaxed);
+        self.residency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the average time the recycled values spent in the dump
+    /// between being thrown and recycled, or None if nothing was recycled.
+    ///
+    /// A short residency means blocks are churned through quickly,
+    /// while a long one means the dump mostly holds idle memory.
+    /// Values taken out by clearing aren't counted.
+    #[cfg(feature = "stats")]
+    pub fn avg_residency(&self) -> Option<Duration> {
+        let count = self.residency_count.load(Ordering::Relaxed);
+        let nanos = self.residency_nanos.load(Ordering::Relaxed);
+
+        nanos.checked_div(count).map(Duration::from_nanos)
+    }
+
+    /// Returns the number of times a recycle came back with nothing
+    /// minus the number of times a throw found the dump full.
+    ///
+    /// Every recycle variant is counted, including [Dump::take] and
+    /// the ones that only get values matching a condition. The ones
+    /// that gave up due to contention aren't.
+    ///
+    /// Positive means consumers are starved, i.e., the dump is often
+    /// drained, and negative means producers are, i.e., the dump is
+    /// often full. A value close to 0 means the capacity fits the load.
+    #[cfg(feature = "stats")]
+    pub fn pressure(&self) -> i64 {
+        self.pressure.load(Ordering::Relaxed)
+    }
+
+    /// Counts a recycle that got `recycled` values, or nothing if it's
+    /// 0, in the stats. All the recycle variants go through this.
+    #[cfg(feature = "stats")]
+    fn count_recycles(&self, recycled: usize) {
+        if recycled == 0 {
+            self.pressure.fetch_add(1, Ordering::Relaxed);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(recycled, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(not(feature = "stats"))]
+    fn count_recycles(&self, _recycled: usize) {}
+
+    /// Returns how many values were recycled and how many times a
+    /// recycle came back with nothing, and how many times a throw
+    /// stored a value or found the dump full. Recycles are counted
+    /// the same way as for [Dump::pressure].
+    ///
+    /// Also returns how many times a compare-and-swap on the bitmaps
+    /// failed due to other threads and was retried, by any operation.
+    /// Always 0 with `mutex-buckets` feature, as there's no retrying.
+    #[cfg(feature = "stats")]
+    pub fn counters(&self) -> FreeListStats {
+        FreeListStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            throws: self.throws.load(Ordering::Relaxed),
+            full_throws: self.full_throws.load(Ordering::Relaxed),
+            cas_retries: self.cas_retries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// This executes closure `f` for every value in the dump
+    /// and clears the dump.
+    ///
+    /// Does the following:
+    /// - Tries to replace reader bitmap with 0
+    /// - Calls f() for each index that was set as per the bitmap.
+    /// - Unsets those indices in writer bitmap.
+    ///
+    /// All the values are claimed by the single update of reader
+    /// bitmap before `f` runs for any of them. A concurrent recycle
+    /// claims its value by unsetting a bit of that same bitmap, so
+    /// a value is either passed to `f` or returned by a recycle,
+    /// never both.
+    ///
+    /// With `mutex-buckets` feature, the bucket lock isn't held
+    /// while `f` runs, so `f` is free to use this dump.
+    pub fn clear(&self, f: impl Fn(*mut T)) {
+        let old_reader_bitmap = match self.take_all() {
+            Some(old_reader_bitmap) => old_reader_bitmap,
+            None => return,
+        };
+
+        let mut old_reader_bitmap_copy = old_reader_bitmap;
+
+        loop {
+            let first_set_spot = old_reader_bitmap_copy.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            old_reader_bitmap_copy &= !B::bit(first_set_spot);
+
+            let val_at_index = self.read_spot(first_set_spot);
+
+            f(val_at_index);
+        }
+
+        self.release(old_reader_bitmap);
+    }
+
+    /// Same as [Dump::clear] but the values are returned instead
+    /// of being passed to a callback.
+    #[cfg(feature = "alloc")]
+    pub fn take(&self) -> Vec<*mut T> {
+        let mut values = Vec::new();
+
+        let old_reader_bitmap = match self.take_all() {
+            Some(old_reader_bitmap) => old_reader_bitmap,
+            None => {
+                self.count_recycles(0);
+                return values;
+            }
+        };
+
+        values.reserve(old_reader_bitmap.count_ones() as usize);
+
+        let mut old_reader_bitmap_copy = old_reader_bitmap;
+
+        loop {
+            let first_set_spot = old_reader_bitmap_copy.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            old_reader_bitmap_copy &= !B::bit(first_set_spot);
+
+            values.push(self.read_spot(first_set_spot));
+        }
+
+        self.release(old_reader_bitmap);
+
+        self.count_recycles(values.len());
+
+        values
+    }
+
+    /// Same as [Dump::clear] but `f` is called for the values
+    /// in ascending order of address.
+    ///
+    /// This helps when the underlying allocator can coalesce
+    /// adjacent blocks that are freed one after the other.
+    /// The values are copied out and sorted on the stack, and
+    /// their spots are released before `f` is ever called.
+    pub fn clear_sorted(&self, f: impl Fn(*mut T)) {
+        let old_reader_bitmap = match self.take_all() {
+            Some(old_reader_bitmap) => old_reader_bitmap,
+            None => return,
+        };
+
+        let mut values = [null_mut::<T>(); W];
+        let mut len = 0;
+        let mut old_reader_bitmap_copy = old_reader_bitmap;
+
+        loop {
+            let first_set_spot = old_reader_bitmap_copy.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            old_reader_bitmap_copy &= !B::bit(first_set_spot);
+
+            values[len] = self.read_spot(first_set_spot);
+            len += 1;
+        }
+
+        self.release(old_reader_bitmap);
+
+        let values = &mut values[..len];
+        values.sort_unstable();
+        values.iter().for_each(|&val| f(val));
+    }
+
+    /// Returns the number of spots that are free to be written,
+    /// i.e., the unset bits in `writer_bitmap` out of the first `W`.
+    ///
+    /// Under concurrent use, this is just a snapshot that may
+    /// already be stale by the time it's returned.
+    pub fn free_slots(&self) -> usize {
+        W - self.writer_bitmap.load(Ordering::Relaxed).count_ones() as usize
+    }
+
+    /// Returns the number of values that can be recycled,
+    /// i.e., the set bits in `reader_bitmap`.
+    ///
+    /// Values that are still being thrown aren't counted. Same
+    /// as [Dump::free_slots], this is just a snapshot.
+    pub fn len(&self) -> usize {
+        self.len_with(Ordering::Relaxed)
+    }
+
+    /// Same as [Dump::len] but `reader_bitmap` is loaded with `ordering`.
+    ///
+    /// `Ordering::Relaxed` is the cheapest and enough for just counting.
+    /// `Ordering::Acquire` is needed if the caller goes on to inspect
+    /// the values that were counted, so that their writes are visible.
+    pub fn len_with(&self, ordering: Ordering) -> usize {
+        self.reader_bitmap.load(ordering).count_ones() as usize
+    }
+
+    /// Returns true if nothing can be recycled from the dump.
+    ///
+    /// Cheaper than comparing [Dump::len] to 0, and `reader_bitmap`
+    /// is loaded with `Ordering::Acquire`. Still just a snapshot.
+    pub fn is_empty(&self) -> bool {
+        self.reader_bitmap.load(Ordering::Acquire) == B::ZERO
+    }
+
+    /// Returns true if every spot is occupied or being written/read,
+    /// so a throw would fail right now. Just a snapshot as well.
+    pub fn is_full(&self) -> bool {
+        self.writer_bitmap.load(Ordering::Relaxed).count_ones() as usize == W
+    }
+
+    /// Returns the current `reader_bitmap`, i.e., the spots that
+    /// hold a value that can be recycled.
+    ///
+    /// This is loaded with `Ordering::Acquire`, so values thrown at
+    /// the set bits are visible to the caller. But under concurrent use,
+    /// the bitmap may change right after it's read, so it's only a
+    /// snapshot meant for advanced users building on top of the dump.
+    pub fn reader_bitmap_snapshot(&self) -> B {
+        self.reader_bitmap.load(Ordering::Acquire)
+    }
+
+    /// Returns the current `writer_bitmap`, i.e., the spots that are
+    /// occupied or being written/read right now.
+    ///
+    /// Same caveats as [Dump::reader_bitmap_snapshot], but this is
+    /// loaded with `Ordering::Relaxed`.
+    pub fn writer_bitmap_snapshot(&self) -> B {
+        self.writer_bitmap.load(Ordering::Relaxed)
+    }
+
+    /// Calls `f` with the values that can currently be recycled,
+    /// without taking them out of the dump.
+    ///
+    /// `reader_bitmap` is loaded once with `Ordering::Acquire` and the
+    /// values at its set bits are copied out on the stack, so `f` sees
+    /// the dump as it was at that point.
+    ///
+    /// # Safety
+    ///
+    /// No recycle or clear may run on this dump concurrently. Once a
+    /// spot is released, a throw can overwrite it while it's being read.
+    pub unsafe fn snapshot(&self, f: impl FnOnce(&[*mut T])) {
+        let mut values = [null_mut::<T>(); W];
+        let mut len = 0;
+        let mut reader_bitmap = self.reader_bitmap_snapshot();
+
+        loop {
+            let first_set_spot = reader_bitmap.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            reader_bitmap &= !B::bit(first_set_spot);
+
+            values[len] = self.read_spot(first_set_spot);
+            len += 1;
+        }
+
+        f(&values[..len])
+    }
+
+    /// [update] on one of the bitmaps of this dump. With `stats` feature,
+    /// every call of `f` after the first one is counted as a retry.
+    #[cfg(not(feature = "stats"))]
+    fn update_bitmap(&self, bitmap: &AtomicBitmap<B>, success: Ordering, f: impl FnMut(B) -> Option<B>) -> Option<B> {
+        update(bitmap, success, f)
+    }
+
+    #[cfg(feature = "stats")]
+    fn update_bitmap(&self, bitmap: &AtomicBitmap<B>, success: Ordering, mut f: impl FnMut(B) -> Option<B>) -> Option<B> {
+        let mut retrying = false;
+
+        update(bitmap, success, |old_bitmap| {
+            if retrying {
+                self.cas_retries.fetch_add(1, Ordering::Relaxed);
+            }
+            retrying = true;
+
+            f(old_bitmap)
+        })
+    }
+
+    /// Replaces `reader_bitmap` with 0 and returns the old one,
+    /// or None if it was already 0.
+    ///
+    /// After this, the values at the set bits of the returned bitmap
+    /// belong to the caller, until it gives the spots back with
+    /// [Dump::release].
+    fn take_all(&self) -> Option<B> {
+        let _guard = self.lock();
+
+        // Same ordering as in `recycle_if()`, the values are read right after
+        self.update_bitmap(&self.reader_bitmap, Ordering::Acquire, |old_reader_bitmap| {
+            if old_reader_bitmap == B::ZERO {
+                None
+            } else {
+                Some(B::ZERO)
+            }
+        })
+    }
+
+    /// Unsets the bits of `spots` in `writer_bitmap`,
+    /// so that they can be written again.
+    ///
+    /// With `strict-clear` feature, debug builds assert that all the
+    /// `spots` were actually set in `writer_bitmap`. A spot taken out of
+    /// `reader_bitmap` can't be released by anyone else, so this holds
+    /// even under concurrent use unless the bitmaps got inconsistent.
+    fn release(&self, spots: B) {
+        let _guard = self.lock();
+
+        // Release for the same reason as in `take_spot()`
+        let old_writer_bitmap = unset_bits(&self.writer_bitmap, spots, Ordering::Release);
+
+        if cfg!(feature = "strict-clear") {
+            debug_assert_eq!(
+                old_writer_bitmap & spots,
+                spots,
+                "spots cleared from reader_bitmap weren't set in writer_bitmap"
+            );
+        }
+    }
+
+    /// Looks for pairs of values for which `is_buddy(a, b)` is true and
+    /// calls `merge(a, b)` for each pair. The pairs that `merge` returns
+    /// true for are taken out of the dump, the rest stay. Returns the
+    /// number of pairs taken out.
+    ///
+    /// All the values are claimed at once like [Dump::clear] does, so
+    /// no recycle gets them while pairs are being looked for. The values
+    /// that stay are published again afterwards, in the same spots.
+    pub fn coalesce(&self, is_buddy: impl Fn(*mut T, *mut T) -> bool, mut merge: impl FnMut(*mut T, *mut T) -> bool) -> usize {
+        let spots = match self.take_all() {
+            Some(spots) => spots,
+            None => return 0,
+        };
+
+        let mut unpaired = spots;
+        let mut merged = B::ZERO;
+
+        loop {
+            let first = unpaired.trailing_zeros();
+
+            if first == B::BITS {
+                break;
+            }
+
+            unpaired &= !B::bit(first);
+
+            let a = self.read_spot(first);
+            let mut rest = unpaired;
+
+            loop {
+                let second = rest.trailing_zeros();
+
+                if second == B::BITS {
+                    break;
+                }
+
+                rest &= !B::bit(second);
+
+                let b = self.read_spot(second);
+
+                if is_buddy(a, b) && merge(a, b) {
+                    unpaired &= !B::bit(second);
+                    merged |= B::bit(first);
+                    merged |= B::bit(second);
+                    break;
+                }
+            }
+        }
+
+        self.release(merged);
+
+        // The spots that stay are still ours, so they're published as they are
+        let _guard = self.lock();
+        set_bits(&self.reader_bitmap, spots & !merged, Ordering::Release);
+
+        (merged.count_ones() / 2) as usize
+    }
+
+    /// Replaces every value in the dump with `f(value)`.
+    ///
+    /// As this takes `&mut self`, no throw or recycle can be in
+    /// progress, so all the set bits in `reader_bitmap` are exactly
+    /// the occupied indices of `dump[]`.
+    pub fn remap(&mut self, f: impl Fn(*mut T) -> *mut T) {
+        let mut reader_bitmap = self.reader_bitmap.load(Ordering::Relaxed);
+
+        loop {
+            let first_set_spot = reader_bitmap.trailing_zeros();
+
+            if first_set_spot == B::BITS {
+                break;
+            }
+
+            reader_bitmap &= !B::bit(first_set_spot);
+
+            self.write_spot(first_set_spot, f(self.read_spot(first_set_spot)));
+        }
+    }
+}
+
+/// A [Dump] for the case where only a single thread ever takes
+/// values out of it, while any number of threads may throw.
+///
+/// As nobody else unsets bits in `reader_bitmap`, a set bit seen
+/// by the consumer stays set until the consumer unsets it. So,
+/// claiming it doesn't need a compare-and-swap loop, a single
+/// `fetch_and` does it. Same goes for releasing the spot in
+/// `writer_bitmap`. This makes [SpmcDump::recycle] wait free.
+///
+/// Throwing works exactly like [Dump::throw].
+pub struct SpmcDump<T, const W: usize = { usize::BITS as usize }, B: BitmapInt = usize>(Dump<T, W, B>);
+
+impl<T, const W: usize, B: BitmapInt> Default for SpmcDump<T, W, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const W: usize, B: BitmapInt> SpmcDump<T, W, B> {
+    const_fn! {
+        /// Returns a new SpmcDump instance.
+        pub fn new() -> Self {
+            SpmcDump(Dump::new())
+        }
+    }
+
+    /// Adds a new element to the dump. Same as [Dump::throw].
+    pub fn throw(&self, raw: *mut T) -> Result<(), *mut T> {
+        self.0.throw(raw)
+    }
+
+    /// Gets a value from the dump. Returns None if dump is empty.
+    ///
+    /// 1) The first set bit in `reader_bitmap` is unset with `fetch_and`.
+    /// 2) Corresponding to that bit posn, we get the `dump[bit_posn]`.
+    /// 3) The same bit is unset from `writer_bitmap` with `fetch_and`.
+    ///
+    /// # Safety
+    ///
+    /// This must not be called concurrently with another
+    /// [SpmcDump::recycle] or [SpmcDump::clear] on the same dump.
+    pub unsafe fn recycle(&self) -> Option<*mut T> {
+        // With `mutex-buckets`, bitmaps are updated with plain stores
+        // under the bucket lock, which a `fetch_and` would race with.
+        if cfg!(feature = "mutex-buckets") {
+            return self.0.recycle();
+        }
+
+        // Pairs with the `Ordering::Release` in `Dump::throw()`
+        let reader_bitmap = self.0.reader_bitmap.load(Ordering::Acquire);
+        let first_set_spot = reader_bitmap.trailing_zeros();
+
+        if first_set_spot == B::BITS {
+            self.0.count_recycles(0);
+            return None;
+        }
+
+        let mask = !B::bit(first_set_spot);
+
+        self.0.reader_bitmap.fetch_and(mask, Ordering::Relaxed);
+
+        Some(self.0.take_spot(first_set_spot))
+    }
+
+    /// Executes `f` for every value in the dump and clears it.
+    /// Same as [Dump::clear].
+    ///
+    /// # Safety
+    ///
+    /// Clearing takes values out of the dump too, so this must not be
+    /// called concurrently with [SpmcDump::recycle] or another
+    /// [SpmcDump::clear] on the same dump.
+    pub unsafe fn clear(&self, f: impl Fn(*mut T)) {
+        self.0.clear(f)
+    }
+}
+
+/// Returns the nanoseconds elapsed since this was first called,
+/// which is how the throw times of values are stored with `stats` feature.
+#[cfg(feature = "stats")]
+fn nanos_since_start() -> u64 {
+    static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+    START.elapsed().as_nanos() as u64
+}
+
+/// Aligns `T` to, and so pads it up to, a cache line of 64 bytes,
+/// which is what most targets have.
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Guard returned by `Dump::lock` when buckets are lock free.
+#[cfg(not(feature = "mutex-buckets"))]
+struct NoLock;
+
+/// Atomically replaces the value in `bitmap` with `f(old)` and
+/// returns `old`. Gives up and returns None as soon as `f` does.
+///
+/// All the compare-and-swap loops on the bitmaps go through this.
+/// After every failed attempt, it backs off a bit, see [Backoff].
+#[cfg(not(feature = "mutex-buckets"))]
+fn update<B: BitmapInt>(bitmap: &AtomicBitmap<B>, success: Ordering, mut f: impl FnMut(B) -> Option<B>) -> Option<B> {
+    let mut old_bitmap = bitmap.load(Ordering::Relaxed);
+    let mut backoff = Backoff::new();
+
+    loop {
+        let new_bitmap = f(old_bitmap)?;
+
+        match bitmap.compare_exchange_weak(old_bitmap, new_bitmap, success, Ordering::Relaxed) {
+            Ok(_) => return Some(old_bitmap),
+            Err(old) => {
+                backoff.spin();
+                old_bitmap = old;
+            }
+        };
+    }
+}
+
+/// Exponential backoff for the compare-and-swap loops.
+///
+/// A failed compare-and-swap means other threads are hammering the
+/// same bitmap. Retrying right away just bounces its cache line
+/// between the cores, so the retry is delayed by a number of spin
+/// loop hints that doubles on every failure, up to a cap.
+#[cfg(not(feature = "mutex-buckets"))]
+struct Backoff {
+    spins: u32,
+}
+
+#[cfg(not(feature = "mutex-buckets"))]
+impl Backoff {
+    const MAX_SPINS: u32 = 64;
+
+    fn new() -> Self {
+        Backoff { spins: 1 }
+    }
+
+    fn spin(&mut self) {
+        for _ in 0..self.spins {
+            crate::sync::spin_loop();
+        }
+
+        self.spins = (self.spins * 2).min(Self::MAX_SPINS);
+    }
+}
+
+/// With `mutex-buckets` feature, the caller holds the bucket lock,
+/// so a plain load and store is all that is needed.
+#[cfg(feature = "mutex-buckets")]
+fn update<B: BitmapInt>(bitmap: &AtomicBitmap<B>, _success: Ordering, f: impl FnOnce(B) -> Option<B>) -> Option<B> {
+    let old_bitmap = bitmap.load(Ordering::Acquire);
+    bitmap.store(f(old_bitmap)?, Ordering::Release);
+    Some(old_bitmap)
+}
+
+/// Same as [update] but makes a single compare-and-swap attempt.
+/// Returns None if `f` gives up, or the result of that attempt, which
+/// may fail due to contention or spuriously.
+#[cfg(not(feature = "mutex-buckets"))]
+fn update_once<B: BitmapInt>(
+    bitmap: &AtomicBitmap<B>,
+    success: Ordering,
+    f: impl FnOnce(B) -> Option<B>,
+) -> Option<Result<B, B>> {
+    let old_bitmap = bitmap.load(Ordering::Relaxed);
+    let new_bitmap = f(old_bitmap)?;
+
+    Some(bitmap.compare_exchange_weak(old_bitmap, new_bitmap, success, Ordering::Relaxed))
+}
+
+#[cfg(feature = "mutex-buckets")]
+fn update_once<B: BitmapInt>(
+    bitmap: &AtomicBitmap<B>,
+    success: Ordering,
+    f: impl FnOnce(B) -> Option<B>,
+) -> Option<Result<B, B>> {
+    update(bitmap, success, f).map(Ok)
+}
+
+/// Returns `bitmap` with its first set bit unset,
+/// or None if no bit is set.
+fn unset_first_set_spot<B: BitmapInt>(bitmap: B) -> Option<B> {
+    let first_set_spot = bitmap.trailing_zeros();
+
+    if first_set_spot == B::BITS {
+        None
+    } else {
+        Some(bitmap & !B::bit(first_set_spot))
+    }
+}
+
+/// Unsets the bits of `mask` in `bitmap` and returns the old bitmap.
+///
+/// This is for bits owned by the caller, that nobody else changes.
+/// So unlike [update], there's no need to look at the old value,
+/// and a single `fetch_and` does it.
+#[cfg(not(feature = "mutex-buckets"))]
+fn unset_bits<B: BitmapInt>(bitmap: &AtomicBitmap<B>, mask: B, order: Ordering) -> B {
+    bitmap.fetch_and(!mask, order)
+}
+
+#[cfg(feature = "mutex-buckets")]
+fn unset_bits<B: BitmapInt>(bitmap: &AtomicBitmap<B>, mask: B, _order: Ordering) -> B {
+    let old_bitmap = bitmap.load(Ordering::Acquire);
+    bitmap.store(old_bitmap & !mask, Ordering::Release);
+    old_bitmap
+}
+
+/// Sets the bits of `mask` in `bitmap` and returns the old bitmap.
+///
+/// Same as [unset_bits], this is for bits owned by the caller,
+/// so a single `fetch_or` does it.
+#[cfg(not(feature = "mutex-buckets"))]
+fn set_bits<B: BitmapInt>(bitmap: &AtomicBitmap<B>, mask: B, order: Ordering) -> B {
+    bitmap.fetch_or(mask, order)
+}
+
+#[cfg(feature = "mutex-buckets")]
+fn set_bits<B: BitmapInt>(bitmap: &AtomicBitmap<B>, mask: B, _order: Ordering) -> B {
+    let old_bitmap = bitmap.load(Ordering::Acquire);
+    bitmap.store(old_bitmap | mask, Ordering::Release);
+    old_bitmap
+}
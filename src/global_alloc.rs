@@ -0,0 +1,165 @@
+#[cfg(feature = "allocator-api")]
+use std::alloc::{AllocError, Allocator};
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "allocator-api")]
+use std::ptr::NonNull;
+
+use crate::freelist::{Error, FreeList, CANARY_PADDING};
+use crate::stats::BucketStats;
+
+/// A [FreeList] on top of [System] that can be used
+/// as the `#[global_allocator]`.
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: FreeListAlloc<16> = FreeListAlloc::new();
+/// ```
+///
+/// Every layout is served from the bucket of
+/// `layout.size().max(layout.align())` rounded up to the next power
+/// of 2. Blocks for a bucket are always allocated from [System] with
+/// the bucket size as both their size and alignment, so any layout
+/// that maps to the bucket can reuse them. As `dealloc` is given
+/// the layout back, no side map of sizes is needed.
+///
+/// Layouts too big for any bucket go to [System] as they are.
+pub struct FreeListAlloc<const N: usize> {
+    list: FreeList<u8, N>,
+}
+
+impl<const N: usize> Default for FreeListAlloc<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FreeListAlloc<N> {
+    /// Returns a new FreeListAlloc with an empty freelist.
+    pub const fn new() -> Self {
+        FreeListAlloc { list: FreeList::new() }
+    }
+
+    /// Returns the number of blocks parked in the freelist.
+    /// See [FreeList::len].
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if no block is parked in the freelist.
+    /// See [FreeList::is_empty].
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns the number of blocks parked in the bucket
+    /// for `size`. See [FreeList::bucket_len].
+    pub fn bucket_len(&self, size: usize) -> Result<usize, Error> {
+        self.list.bucket_len(size)
+    }
+
+    /// Returns the number of bytes parked in the freelist.
+    /// See [FreeList::total_bytes_held].
+    pub fn total_bytes_held(&self) -> usize {
+        self.list.total_bytes_held()
+    }
+
+    /// Returns a [BucketStats] for every bucket of the freelist.
+    /// See [FreeList::bucket_stats].
+    pub fn bucket_stats(&self) -> Vec<BucketStats> {
+        self.list.bucket_stats()
+    }
+
+    /// Gives the blocks parked in the freelist back to [System].
+    ///
+    /// The freelist itself isn't handed out, as a block thrown
+    /// into it from outside would be returned by `alloc`.
+    pub fn clear(&self) {
+        self.list.clear(|ptr, power| {
+            let size = 1 << power;
+
+            // Same layout the block was allocated with, see block_layout
+            unsafe { System.dealloc(ptr, Layout::from_size_align_unchecked(size + CANARY_PADDING, size)) }
+        });
+    }
+
+    /// Returns the bucket size for `layout` along with the layout
+    /// blocks of that bucket are allocated from [System] with.
+    ///
+    /// Returns None if `layout` doesn't fit in any bucket.
+    fn block_layout(layout: Layout) -> Option<(usize, Layout)> {
+        let size = layout.size().max(layout.align()).checked_next_power_of_two()?;
+
+        if size.trailing_zeros() as usize >= N {
+            return None;
+        }
+
+        let block_layout = Layout::from_size_align(size.checked_add(CANARY_PADDING)?, size).ok()?;
+
+        Some((size, block_layout))
+    }
+
+    /// Returns a block for `layout` along with its actual size,
+    /// from the freelist if possible, otherwise from [System].
+    unsafe fn alloc_block(&self, layout: Layout) -> (*mut u8, usize) {
+        let (size, block_layout) = match Self::block_layout(layout) {
+            Some(block) => block,
+            None => return (System.alloc(layout), layout.size()),
+        };
+
+        self.list
+            .get_or_alloc(size, |_| System.alloc(block_layout), |ptr| System.dealloc(ptr, block_layout))
+    }
+
+    /// Parks the block for `layout` in the freelist, or gives it
+    /// back to [System] if it can't be parked.
+    unsafe fn dealloc_block(&self, ptr: *mut u8, layout: Layout) {
+        match Self::block_layout(layout) {
+            Some((size, block_layout)) => {
+                self.list.put_or_free(ptr, size, |ptr| System.dealloc(ptr, block_layout));
+            }
+            None => System.dealloc(ptr, layout),
+        }
+    }
+}
+
+unsafe impl<const N: usize> GlobalAlloc for FreeListAlloc<N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.alloc_block(layout).0
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.dealloc_block(ptr, layout)
+    }
+}
+
+/// Lets collections allocate from the freelist, e.g.
+/// `Vec::new_in(&alloc)`. The returned blocks are as big as their
+/// bucket, so collections can make use of the whole block.
+///
+/// Zero sized layouts get a dangling pointer and never
+/// touch the freelist.
+#[cfg(feature = "allocator-api")]
+// Only builds on nightly, so the MSRV doesn't apply
+#[allow(clippy::incompatible_msrv)]
+unsafe impl<const N: usize> Allocator for &FreeListAlloc<N> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            // Alignment is never 0, so it's a valid dangling address
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        let (ptr, size) = unsafe { self.alloc_block(layout) };
+
+        NonNull::new(ptr)
+            .map(|ptr| NonNull::slice_from_raw_parts(ptr, size))
+            .ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.dealloc_block(ptr.as_ptr(), layout)
+        }
+    }
+}
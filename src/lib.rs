@@ -1,5 +1,7 @@
 #![deny(warnings)]
 #![deny(missing_docs)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! [FreeList] type provided by this crate can be
 //! on top of allocators to reuse allocated memory.
@@ -17,11 +19,75 @@
 //! mbedtls crate.
 //!
 //! Otherwise, it maybe used in a global_allocator.
+//!
+//! [FreeList] and [Dump] only need `core`, so with
+//! `default-features = false` the crate builds for `#![no_std]`
+//! targets. The `alloc` feature adds the methods that return a `Vec`,
+//! like `FreeList::take_bucket`, and `Pool`. The `std` feature (on by
+//! default) implies `alloc` and adds the `std::error::Error` impl for
+//! [Error], and the features that need the OS, like `calloc` and
+//! `stats`, turn it on themselves.
+//! Without `std`, the `core-error` feature implements
+//! `core::error::Error` for [Error] instead, on rust 1.81 or later.
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[macro_use]
+mod sync;
+
+mod bitmap;
+pub use bitmap::BitmapInt;
 
 mod freelist;
 pub use freelist::*;
 
-#[cfg(feature = "calloc")]
+// When built with `--cfg loom`, dumps can't be created in const contexts,
+// which the types below need, so only the dumps and FreeList are built.
+#[cfg(not(loom))]
+mod size_class;
+#[cfg(not(loom))]
+pub use size_class::*;
+
+#[cfg(all(feature = "std", not(loom)))]
+mod global_alloc;
+#[cfg(all(feature = "std", not(loom)))]
+pub use global_alloc::*;
+
+#[cfg(not(loom))]
+mod segmented;
+#[cfg(not(loom))]
+pub use segmented::*;
+
+#[cfg(all(feature = "alloc", not(loom)))]
+mod pool;
+#[cfg(all(feature = "alloc", not(loom)))]
+pub use pool::*;
+
+mod stats;
+pub use stats::*;
+
+#[cfg(all(
+    feature = "madvise",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )
+))]
+mod madvise;
+
+#[cfg(all(feature = "mlock", unix))]
+mod mlock;
+
+#[cfg(feature = "hot-swap")]
+mod cell;
+#[cfg(feature = "hot-swap")]
+pub use cell::*;
+
+#[cfg(all(feature = "calloc", not(loom)))]
 /// Provides calloc/free wrappers that use
 /// [FreeList] type.
 pub mod calloc;
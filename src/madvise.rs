@@ -0,0 +1,34 @@
+use libc::{c_void, madvise, sysconf, _SC_PAGESIZE, MADV_FREE};
+
+use crate::bitmap::BitmapInt;
+use crate::freelist::{Error, FreeList};
+
+impl<T, const N: usize, const W: usize, B: BitmapInt> FreeList<T, N, W, B> {
+    /// Same as [FreeList::clear_bucket] but before `f` runs for
+    /// a block, the pages lying entirely inside the block are
+    /// `madvise`d with `MADV_FREE`.
+    ///
+    /// This lets the OS reclaim the memory of large blocks right
+    /// away, even if the underlying allocator holds on to it after
+    /// `f` frees it. Blocks smaller than a page are left alone as
+    /// they can't contain a whole page.
+    pub fn clear_bucket_madvise(&self, size: usize, f: impl Fn(*mut T)) -> Result<(), Error> {
+        let page_size = unsafe { sysconf(_SC_PAGESIZE) } as usize;
+
+        self.clear_bucket(size, |ptr| {
+            advise_free(ptr as usize, size, page_size);
+            f(ptr)
+        })
+    }
+}
+
+/// `madvise`s the pages lying entirely in `[addr, addr + len)`
+/// with `MADV_FREE`. It is just a hint, so failures are ignored.
+fn advise_free(addr: usize, len: usize, page_size: usize) {
+    let start = (addr + page_size - 1) & !(page_size - 1);
+    let end = (addr + len) & !(page_size - 1);
+
+    if start < end {
+        unsafe { madvise(start as *mut c_void, end - start, MADV_FREE) };
+    }
+}
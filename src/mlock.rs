@@ -0,0 +1,64 @@
+use std::cell::Cell;
+use std::io;
+
+use libc::{c_void, mlock, munlock};
+
+use crate::bitmap::BitmapInt;
+use crate::freelist::FreeList;
+
+impl<T, const N: usize, const W: usize, B: BitmapInt> FreeList<T, N, W, B> {
+    /// `mlock`s every block currently parked in the freelist, so that
+    /// the pages of recycled blocks stay resident and handing them
+    /// out never incurs a page fault.
+    ///
+    /// Locking is attempted for every block even if some fail, and
+    /// the first error is returned. Typical failures are `ENOMEM` when
+    /// `RLIMIT_MEMLOCK` is exceeded and `EPERM` for unprivileged
+    /// processes on some systems (`CAP_IPC_LOCK` lifts both on Linux).
+    ///
+    /// Pages are locked as a whole, so pages shared with other
+    /// memory get locked too. Memory locks don't stack, one
+    /// [FreeList::munlock_blocks] undoes any number of these.
+    ///
+    /// # Safety
+    ///
+    /// Every parked block must be valid for its bucket size,
+    /// and as for [FreeList::snapshot_all], no recycle or clear
+    /// may run concurrently.
+    pub unsafe fn mlock_blocks(&self) -> io::Result<()> {
+        self.for_each_block(|addr, len| mlock(addr, len))
+    }
+
+    /// Undoes [FreeList::mlock_blocks] for the blocks currently
+    /// parked in the freelist. Blocks should be unlocked before
+    /// they are freed, if the underlying allocator reuses them.
+    ///
+    /// Same failure handling as [FreeList::mlock_blocks].
+    ///
+    /// # Safety
+    ///
+    /// Same as [FreeList::mlock_blocks].
+    pub unsafe fn munlock_blocks(&self) -> io::Result<()> {
+        self.for_each_block(|addr, len| munlock(addr, len))
+    }
+
+    /// Calls `f(addr, len)` for every parked block and returns the
+    /// error for the first call that returns nonzero.
+    unsafe fn for_each_block(&self, f: impl Fn(*const c_void, usize) -> i32) -> io::Result<()> {
+        let first_error = Cell::new(None);
+
+        self.snapshot_all(|size, ptrs| {
+            for &ptr in ptrs {
+                if f(ptr as *const c_void, size) != 0 {
+                    let error = first_error.take().unwrap_or_else(io::Error::last_os_error);
+                    first_error.set(Some(error));
+                }
+            }
+        });
+
+        match first_error.into_inner() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use core::mem::{align_of, size_of};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+
+use crate::freelist::{FreeList, CANARY_PADDING};
+
+/// A pool of heap allocated `T`s on top of a [FreeList].
+///
+/// [Pool::get] hands out a `T` in a block recycled from the freelist,
+/// or in a newly allocated one if there is none. Dropping the returned
+/// [PoolGuard] drops the `T` and parks its block back in the freelist.
+///
+/// All the blocks go to the single bucket for
+/// `size_of::<T>().next_power_of_two()`. If that's more than
+/// [FreeList::MAX_SIZE], nothing is ever parked and every `get`
+/// allocates. Parked blocks are freed when the pool is dropped.
+pub struct Pool<T, const N: usize> {
+    list: FreeList<T, N>,
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    /// Returns a new empty Pool.
+    pub const fn new() -> Self {
+        Pool { list: FreeList::new() }
+    }
+
+    /// Returns the number of blocks parked in the pool.
+    ///
+    /// The freelist itself isn't handed out, as [Pool::get]
+    /// writes into whatever block it recycles from it.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if no block is parked in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns a guard to `init()` written in a block from
+    /// the freelist if there is one, otherwise in a new block.
+    pub fn get(&self, init: impl FnOnce() -> T) -> PoolGuard<'_, T, N> {
+        let (ptr, _) = self.list.get_or_alloc(
+            Self::block_size(),
+            |_| {
+                let ptr = unsafe { alloc(Self::block_layout()) };
+
+                if ptr.is_null() {
+                    handle_alloc_error(Self::block_layout());
+                }
+
+                ptr as *mut T
+            },
+            // Rejected by FreeList::recycle, e.g. for a broken canary
+            |ptr| unsafe { dealloc(ptr as *mut u8, Self::block_layout()) },
+        );
+
+        // Parked blocks hold no value, so nothing is dropped here
+        unsafe { ptr.write(init()) };
+
+        PoolGuard { ptr, pool: self }
+    }
+
+    /// Same as [Pool::get] but the value is a clone of `template`,
+    /// which is written straight into the block.
+    ///
+    /// A guard is returned rather than a `Box<T>`, as the blocks are
+    /// allocated with a layout of their own that `Box` can't free.
+    pub fn take_clone(&self, template: &T) -> PoolGuard<'_, T, N>
+    where
+        T: Clone,
+    {
+        self.get(|| template.clone())
+    }
+
+    /// Size of the bucket the blocks go to.
+    fn block_size() -> usize {
+        size_of::<T>().next_power_of_two()
+    }
+
+    /// Layout every block is allocated with, with room
+    /// for the canary of the `canary` feature.
+    fn block_layout() -> Layout {
+        Layout::from_size_align(Self::block_size() + CANARY_PADDING, align_of::<T>()).unwrap()
+    }
+
+    /// Drops the value in `ptr` and parks the block,
+    /// or frees it if the bucket is full.
+    unsafe fn put(&self, ptr: *mut T) {
+        ptr::drop_in_place(ptr);
+
+        self.list
+            .put_or_free(ptr, Self::block_size(), |ptr| dealloc(ptr as *mut u8, Self::block_layout()));
+    }
+}
+
+impl<T, const N: usize> Drop for Pool<T, N> {
+    fn drop(&mut self) {
+        self.list
+            .clear(|ptr, _| unsafe { dealloc(ptr as *mut u8, Self::block_layout()) });
+    }
+}
+
+/// A `T` handed out by [Pool::get]. Dropping it drops
+/// the `T` and parks its block back in the pool.
+pub struct PoolGuard<'a, T, const N: usize> {
+    ptr: *mut T,
+    pool: &'a Pool<T, N>,
+}
+
+impl<T, const N: usize> Deref for PoolGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T, const N: usize> DerefMut for PoolGuard<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr }
+    }
+}
+
+impl<T, const N: usize> Drop for PoolGuard<'_, T, N> {
+    fn drop(&mut self) {
+        unsafe { self.pool.put(self.ptr) }
+    }
+}
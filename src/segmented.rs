@@ -0,0 +1,77 @@
+use crate::freelist::Dump;
+
+/// A dump that holds `S * usize::BITS` values by chaining `S`
+/// [Dump]s, each of which covers one word of the bitmaps.
+///
+/// [Dump::throw] and [Dump::recycle] are tried on the words in
+/// order, so values are packed into the lower words first. The
+/// segmented dump is full when every word is full, and empty
+/// when every word is empty.
+///
+/// Useful when more than `usize::BITS` values need to be
+/// retained, e.g. 128 pointers per size class on 64 bit.
+pub struct SegmentedDump<T, const S: usize>([Dump<T>; S]);
+
+impl<T, const S: usize> Default for SegmentedDump<T, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const S: usize> SegmentedDump<T, S> {
+    // Only used as operand of array repeat expression in `new()`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_DUMP: Dump<T> = Dump::new();
+
+    /// Returns a new SegmentedDump instance.
+    pub const fn new() -> Self {
+        SegmentedDump([Self::EMPTY_DUMP; S])
+    }
+
+    /// Returns the number of values the dump can hold,
+    /// i.e., `S * usize::BITS`.
+    pub const fn capacity() -> usize {
+        S * Dump::<T>::capacity()
+    }
+
+    /// Adds a new element to the first word that has a free spot.
+    ///
+    /// Returns Err(raw) if every word is full.
+    pub fn throw(&self, raw: *mut T) -> Result<(), *mut T> {
+        for dump in self.0.iter() {
+            if dump.throw(raw).is_ok() {
+                return Ok(());
+            }
+        }
+
+        Err(raw)
+    }
+
+    /// Gets a value from the first word that has one.
+    /// Returns None if every word is empty.
+    pub fn recycle(&self) -> Option<*mut T> {
+        self.0.iter().find_map(|dump| dump.recycle())
+    }
+
+    /// Executes closure `f` for every value in the dump
+    /// and clears the dump. See [Dump::clear].
+    pub fn clear(&self, f: impl Fn(*mut T)) {
+        self.0.iter().for_each(|dump| dump.clear(&f))
+    }
+
+    /// Returns the number of values that can be recycled.
+    /// Just a snapshot, same as [Dump::len].
+    pub fn len(&self) -> usize {
+        self.0.iter().map(Dump::len).sum()
+    }
+
+    /// Returns true if every word is empty. Just a snapshot.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(Dump::is_empty)
+    }
+
+    /// Returns true if every word is full. Just a snapshot.
+    pub fn is_full(&self) -> bool {
+        self.0.iter().all(Dump::is_full)
+    }
+}
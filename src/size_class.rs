@@ -0,0 +1,122 @@
+use crate::freelist::{Dump, Error};
+
+/// A freelist whose power of 2 buckets are further divided into
+/// `S` size classes.
+///
+/// In a [FreeList](crate::FreeList), a request for 1025 bytes and one
+/// for 2047 bytes both go to the 2048 byte bucket, so blocks of both
+/// get mixed and every block has to be 2048 bytes to be reusable.
+/// Here, the sizes (1024, 2048] going to the 2048 byte bucket are
+/// further split into `S` equal classes. For example, if S is 4:
+/// 1) class for (1024, 1280]
+/// 2) class for (1280, 1536]
+/// 3) class for (1536, 1792]
+/// 4) class for (1792, 2048]
+///
+/// Each class has its own [Dump], and a block only needs to be as
+/// big as the upper end of its class, which is what
+/// [SizeClassFreeList::class_size] returns. So, the caller should
+/// always allocate `class_size(size)` bytes for a request of `size`.
+///
+/// `S` must be a power of 2. Buckets smaller than `2 * S` bytes
+/// just have fewer classes, one for each size.
+pub struct SizeClassFreeList<T, const N: usize, const S: usize>([[Dump<T>; S]; N]);
+
+impl<T, const N: usize, const S: usize> Default for SizeClassFreeList<T, N, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, const S: usize> SizeClassFreeList<T, N, S> {
+    // Only used as operands of array repeat expressions in `new()`
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_DUMP: Dump<T> = Dump::new();
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_BUCKET: [Dump<T>; S] = [Self::EMPTY_DUMP; S];
+
+    /// Initialize a freelist with empty classes.
+    pub const fn new() -> Self {
+        assert!(S.is_power_of_two(), "number of size classes should be power of 2");
+
+        SizeClassFreeList([Self::EMPTY_BUCKET; N])
+    }
+
+    /// Returns the upper end of the class that `size` falls in.
+    /// Blocks for `size` should be allocated with this size.
+    ///
+    /// Returns BucketNotAvailable if `size` is too big for this freelist.
+    pub fn class_size(&self, size: usize) -> Result<usize, Error> {
+        let (power, class) = Self::class_of(size)?;
+        let (half, step) = Self::half_and_step(power);
+
+        Ok(half + (class + 1) * step)
+    }
+
+    /// Expects a size (not necessarily a power of 2) and returns a
+    /// pointer from its class if available.
+    ///
+    /// Returns BucketEmpty if nothing is available in the class.
+    /// Returns BucketNotAvailable if `size` is too big for this freelist.
+    pub fn recycle(&self, size: usize) -> Result<*mut T, Error> {
+        let (power, class) = Self::class_of(size)?;
+
+        self.0[power][class].recycle().ok_or(Error::BucketEmpty)
+    }
+
+    /// Throws the given pointer into the class of `size`.
+    ///
+    /// Returns BucketFull if the class is full.
+    /// Returns BucketNotAvailable if `size` is too big for this freelist.
+    pub fn throw(&self, ptr: *mut T, size: usize) -> Result<(), Error> {
+        let (power, class) = Self::class_of(size)?;
+
+        self.0[power][class].throw(ptr).map_err(|_| Error::BucketFull)
+    }
+
+    /// Clears the freelist.
+    ///
+    /// f(ptr, class_size)
+    ///   ptr:
+    ///     ptr to free
+    ///   class_size:
+    ///     size of the block in bytes,
+    ///     see [SizeClassFreeList::class_size].
+    pub fn clear(&self, f: impl Fn(*mut T, usize)) {
+        for (power, bucket) in self.0.iter().enumerate() {
+            let (half, step) = Self::half_and_step(power);
+
+            for (class, dump) in bucket.iter().enumerate() {
+                dump.clear(|ptr| f(ptr, half + (class + 1) * step))
+            }
+        }
+    }
+
+    /// Returns the bucket index (power of 2) and the class
+    /// within that bucket for `size`.
+    fn class_of(size: usize) -> Result<(usize, usize), Error> {
+        let bucket_size = size.checked_next_power_of_two().ok_or(Error::BucketNotAvailable)?;
+        let power = bucket_size.trailing_zeros() as usize;
+
+        if power >= N {
+            return Err(Error::BucketNotAvailable);
+        }
+
+        let (half, step) = Self::half_and_step(power);
+
+        // Only the 1 byte bucket has no lower half
+        if half == 0 {
+            return Ok((power, 0));
+        }
+
+        Ok((power, (size - half - 1) / step))
+    }
+
+    /// For the bucket of `1 << power` bytes, returns the exclusive
+    /// lower end of its sizes and the width of each of its classes.
+    fn half_and_step(power: usize) -> (usize, usize) {
+        let half = (1 << power) / 2;
+
+        (half, (half / S).max(1))
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "stats")]
+use core::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::bitmap::BitmapInt;
+use crate::freelist::FreeList;
+
+/// Snapshot of a single bucket returned by [FreeList::bucket_stats].
+///
+/// Only holds counts and sizes, never the parked pointers, so
+/// with `serde` feature it can be serialized as is for exporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BucketStats {
+    /// Size of the blocks in the bucket
+    pub size: usize,
+    /// Number of blocks that can be recycled, see [FreeList::len]
+    pub parked: usize,
+    /// Number of blocks that can still be thrown, see [FreeList::free_slots]
+    pub free_slots: usize,
+    /// See [FreeList::avg_residency]
+    #[cfg(feature = "stats")]
+    pub avg_residency: Option<Duration>,
+    /// See [FreeList::pressure]
+    #[cfg(feature = "stats")]
+    pub pressure: i64,
+    /// See [Dump::counters](crate::Dump::counters)
+    #[cfg(feature = "stats")]
+    pub counters: FreeListStats,
+}
+
+/// Counts of recycles and throws, either of a single bucket
+/// (see [Dump::counters](crate::Dump::counters)) or of all of them added up
+/// (see [FreeList::stats]).
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FreeListStats {
+    /// Pointers recycled
+    pub hits: usize,
+    /// Recycles that got nothing
+    pub misses: usize,
+    /// Throws that stored the pointer
+    pub throws: usize,
+    /// Throws that found the bucket full
+    pub full_throws: usize,
+    /// Compare-and-swaps on the bitmaps that were retried
+    /// due to contention
+    pub cas_retries: usize,
+}
+
+impl<T, const N: usize, const W: usize, B: BitmapInt> FreeList<T, N, W, B> {
+    /// Returns a [BucketStats] for every bucket, smallest first.
+    ///
+    /// Like the queries it's made of, this is just a snapshot
+    /// under concurrent use.
+    #[cfg(feature = "alloc")]
+    pub fn bucket_stats(&self) -> Vec<BucketStats> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(idx, dump)| BucketStats {
+                size: 1 << idx,
+                parked: dump.len(),
+                free_slots: dump.free_slots(),
+                #[cfg(feature = "stats")]
+                avg_residency: dump.avg_residency(),
+                #[cfg(feature = "stats")]
+                pressure: dump.pressure(),
+                #[cfg(feature = "stats")]
+                counters: dump.counters(),
+            })
+            .collect()
+    }
+
+    /// Returns the counters of all the buckets added up.
+    ///
+    /// The per bucket counters are in [FreeList::bucket_stats].
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> FreeListStats {
+        self.buckets
+            .iter()
+            .map(|dump| dump.counters())
+            .fold(FreeListStats::default(), |total, counters| FreeListStats {
+                hits: total.hits + counters.hits,
+                misses: total.misses + counters.misses,
+                throws: total.throws + counters.throws,
+                full_throws: total.full_throws + counters.full_throws,
+                cas_retries: total.cas_retries + counters.cas_retries,
+            })
+    }
+}
@@ -0,0 +1,54 @@
+//! Atomics and cells that [Dump](crate::Dump) is built on.
+//!
+//! When built with `--cfg loom`, these are loom's, so that the model checker
+//! sees every access to the bitmaps and to the values in a dump.
+//! Otherwise they are the core ones and [UnsafeCell] is a thin wrapper
+//! giving core's cell the same API as loom's.
+
+#[cfg(all(not(loom), target_has_atomic = "64"))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+#[cfg(all(loom, target_has_atomic = "64"))]
+pub(crate) use loom::sync::atomic::AtomicU64;
+#[cfg(loom)]
+pub(crate) use loom::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering},
+};
+
+// Only the compare-and-swap loops spin, which `mutex-buckets` replaces
+#[cfg(all(not(loom), not(feature = "mutex-buckets")))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(all(loom, not(feature = "mutex-buckets")))]
+pub(crate) use loom::hint::spin_loop;
+
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) const fn new(data: T) -> Self {
+        UnsafeCell(core::cell::UnsafeCell::new(data))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// Defines a fn that is `const` unless built with `--cfg loom`,
+/// as loom's atomics can't be created in a const context.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])* $vis fn $($rest)*
+    };
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "allocator-api")]
+#![feature(allocator_api)]
+
+use std::alloc::{Allocator, Layout};
+
+use freelist::FreeListAlloc;
+
+#[test]
+fn blocks_are_as_big_as_their_bucket() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layout = Layout::from_size_align(20, 4).unwrap();
+
+    let block = (&alloc).allocate(layout).unwrap();
+    assert_eq!(block.len(), 32);
+
+    unsafe { (&alloc).deallocate(block.cast(), layout) };
+    assert_eq!(alloc.bucket_len(32).unwrap(), 1);
+}
+
+#[test]
+fn vecs_reuse_parked_blocks() {
+    let alloc = FreeListAlloc::<8>::new();
+
+    let mut vec: Vec<u8, _> = Vec::with_capacity_in(20, &alloc);
+    vec.extend(0..20);
+    let ptr = vec.as_ptr();
+    drop(vec);
+
+    assert_eq!(alloc.bucket_len(32).unwrap(), 1);
+
+    let vec: Vec<u8, _> = Vec::with_capacity_in(32, &alloc);
+    assert_eq!(vec.as_ptr(), ptr);
+    assert!(alloc.is_empty());
+}
+
+#[test]
+fn boxes_go_back_to_the_freelist_on_drop() {
+    let alloc = FreeListAlloc::<8>::new();
+
+    let boxed = Box::new_in([7u64; 4], &alloc);
+    assert_eq!(*boxed, [7; 4]);
+    drop(boxed);
+
+    assert_eq!(alloc.bucket_len(32).unwrap(), 1);
+}
+
+#[test]
+fn zero_sized_layouts_never_touch_the_freelist() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layout = Layout::from_size_align(0, 16).unwrap();
+
+    let block = (&alloc).allocate(layout).unwrap();
+    assert_eq!(block.len(), 0);
+    assert_eq!(block.cast::<u8>().as_ptr() as usize % 16, 0);
+
+    unsafe { (&alloc).deallocate(block.cast(), layout) };
+    assert!(alloc.is_empty());
+    assert_eq!(alloc.total_bytes_held(), 0);
+}
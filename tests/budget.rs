@@ -0,0 +1,169 @@
+mod common;
+
+use common::{adjacent_blocks, block, blocks};
+use freelist::{Error, FreeList, CANARY_PADDING};
+
+#[test]
+fn throws_past_the_budget_are_rejected() {
+    let list = FreeList::<u8, 8>::new();
+    list.set_max_bytes(64);
+
+    for ptr in blocks(2, 32) {
+        list.throw(ptr, 32).unwrap();
+    }
+
+    assert!(matches!(list.throw(block(1), 1), Err(Error::BudgetExceeded)));
+    assert_eq!(list.total_bytes_held(), 64);
+    assert_eq!(list.bucket_len(1).unwrap(), 0);
+}
+
+#[test]
+fn recycles_make_room_again() {
+    let list = FreeList::<u8, 8>::new();
+    list.set_max_bytes(32);
+
+    list.throw(block(32), 32).unwrap();
+    assert!(matches!(list.throw(block(16), 16), Err(Error::BudgetExceeded)));
+
+    list.recycle(32).unwrap();
+    assert_eq!(list.total_bytes_held(), 0);
+
+    list.throw(block(16), 16).unwrap();
+    list.throw(block(16), 16).unwrap();
+    assert_eq!(list.total_bytes_held(), 32);
+}
+
+#[test]
+fn failed_throws_dont_count() {
+    let list = FreeList::<u8, 2>::new();
+
+    for ptr in blocks(list.free_slots_for(2).unwrap(), 2) {
+        list.throw(ptr, 2).unwrap();
+    }
+
+    let held = list.total_bytes_held();
+
+    assert!(matches!(list.throw(block(2), 2), Err(Error::BucketFull)));
+    assert!(matches!(list.throw(block(4), 4), Err(Error::BucketNotAvailable)));
+    assert_eq!(list.total_bytes_held(), held);
+}
+
+#[test]
+fn every_way_out_of_the_buckets_counts() {
+    let list = FreeList::<u8, 8>::new();
+    let throw = |count, size| blocks(count, size).into_iter().for_each(|ptr| list.throw(ptr, size).unwrap());
+
+    throw(4, 8);
+    list.try_recycle_once(8).unwrap();
+    list.recycle_with_slot(8).unwrap();
+    list.recycle_if_above(8, 0).unwrap().unwrap();
+    list.recycle_aligned(8, 1).unwrap();
+    assert_eq!(list.total_bytes_held(), 0);
+
+    throw(2, 16);
+    assert_eq!(list.recycle_within(4, 8).unwrap().1, 16);
+    assert_eq!(list.drain_bucket(16).unwrap().count(), 1);
+    assert_eq!(list.total_bytes_held(), 0);
+
+    throw(3, 32);
+    assert_eq!(list.reclaim_until(32, |_, _| {}), 32);
+    list.clear_bucket(32, |_| {}).unwrap();
+    assert_eq!(list.total_bytes_held(), 0);
+
+    throw(2, 4);
+    throw(2, 64);
+    list.clear_sorted(|_, _| {});
+    assert_eq!(list.total_bytes_held(), 0);
+
+    throw(2, 4);
+    list.drain_and_seal(|_, _| {});
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn batched_ways_out_count() {
+    let list = FreeList::<u8, 8>::new();
+
+    for ptr in blocks(5, 16) {
+        list.throw(ptr, 16).unwrap();
+    }
+
+    let mut local = Vec::new();
+    assert_eq!(list.recycle_refill(16, &mut local, 2, |_, _| unreachable!()).unwrap(), 2);
+    assert_eq!(list.total_bytes_held(), 3 * 16);
+
+    assert_eq!(list.take_bucket(16).unwrap().len(), 3);
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn coalesced_blocks_are_counted_once() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = adjacent_blocks(2, 16);
+
+    list.throw(ptrs[0], 16).unwrap();
+    list.throw(ptrs[1], 16).unwrap();
+
+    let merged = list.try_coalesce(16, |a, b| a.max(b) as usize - a.min(b) as usize == 16 + CANARY_PADDING);
+
+    assert_eq!(merged.unwrap(), 1);
+    assert_eq!(list.total_bytes_held(), 32);
+    assert_eq!(list.bucket_len(32).unwrap(), 1);
+}
+
+#[test]
+fn coalescing_within_the_budget_isnt_rejected() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = adjacent_blocks(2, 16);
+
+    list.throw(ptrs[0], 16).unwrap();
+    list.throw(ptrs[1], 16).unwrap();
+
+    // The merged block replaces both halves, so it fits exactly
+    list.set_max_bytes(32);
+
+    assert_eq!(list.try_coalesce(16, |_, _| true).unwrap(), 1);
+    assert_eq!(list.total_bytes_held(), 32);
+}
+
+#[test]
+fn bytes_held_add_up_over_the_buckets() {
+    let list = FreeList::<u8, 8>::new();
+    assert_eq!(list.total_bytes_held(), 0);
+
+    blocks(3, 4).into_iter().for_each(|ptr| list.throw(ptr, 4).unwrap());
+    blocks(2, 128).into_iter().for_each(|ptr| list.throw(ptr, 128).unwrap());
+
+    assert_eq!(list.bytes_held_in_bucket(4).unwrap(), 12);
+    assert_eq!(list.bytes_held_in_bucket(128).unwrap(), 256);
+    assert_eq!(list.bytes_held_in_bucket(64).unwrap(), 0);
+    assert_eq!(list.total_bytes_held(), 12 + 256);
+
+    list.recycle(128).unwrap();
+    assert_eq!(list.bytes_held_in_bucket(128).unwrap(), 128);
+    assert_eq!(list.total_bytes_held(), 12 + 128);
+}
+
+#[test]
+fn bytes_held_in_bucket_needs_a_bucket() {
+    let list = FreeList::<u8, 8>::new();
+
+    assert!(matches!(list.bytes_held_in_bucket(12), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.bytes_held_in_bucket(256), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn a_full_freelist_holds_every_bucket_at_capacity() {
+    let list = FreeList::<u8, 3>::new();
+    let per_bucket = list.free_slots_for(1).unwrap();
+
+    for size in [1, 2, 4] {
+        blocks(per_bucket, size)
+            .into_iter()
+            .for_each(|ptr| list.throw(ptr, size).unwrap());
+    }
+
+    assert_eq!(list.total_bytes_held(), per_bucket * (1 + 2 + 4));
+    assert_eq!(list.bytes_held_in_bucket(4).unwrap(), per_bucket * 4);
+}
@@ -0,0 +1,539 @@
+#![cfg(feature = "calloc")]
+
+use std::{
+    cell::RefCell,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use freelist::calloc::{
+    aligned_calloc, calloc, calloc_with, clear_epoch, clear_freelist_with, free, free_with, malloc, realloc, LibcAlloc,
+    UnderlyingAlloc, CALLOC_MIN_CACHE_SIZE,
+};
+use freelist::CANARY_PADDING;
+use libc::{c_void, size_t};
+use once_cell::sync::Lazy;
+
+/// The freelist of the module is shared by every test, so the ones
+/// that look at what it holds take turns, each with nothing parked.
+fn serial() -> MutexGuard<'static, ()> {
+    static LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+    let guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    unsafe { clear_freelist_with(&LibcAlloc) };
+    guard
+}
+
+/// Returns `size`, or the smallest size the freelist is used
+/// for, if it was set higher at build time.
+fn cached(size: usize) -> usize {
+    #[allow(clippy::unnecessary_min_or_max)] // CALLOC_MIN_CACHE_SIZE is 0 by default
+    size.max(CALLOC_MIN_CACHE_SIZE)
+}
+
+/// Goes to libc and remembers what reached it.
+#[derive(Default)]
+struct Recording {
+    callocs: RefCell<Vec<(size_t, size_t)>>,
+    frees: RefCell<Vec<usize>>,
+}
+
+impl UnderlyingAlloc for Recording {
+    fn calloc(&self, nmemb: size_t, size: size_t) -> *mut c_void {
+        self.callocs.borrow_mut().push((nmemb, size));
+        LibcAlloc.calloc(nmemb, size)
+    }
+
+    unsafe fn free(&self, ptr: *mut c_void) {
+        self.frees.borrow_mut().push(ptr as usize);
+        LibcAlloc.free(ptr)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut c_void, size: size_t) -> *mut c_void {
+        LibcAlloc.realloc(ptr, size)
+    }
+}
+
+#[test]
+fn clear_epoch_counts_the_clears() {
+    let _serial = serial();
+    let before = clear_epoch();
+
+    unsafe { clear_freelist_with(&LibcAlloc) };
+    unsafe { clear_freelist_with(&LibcAlloc) };
+
+    assert_eq!(clear_epoch(), before + 2);
+}
+
+#[test]
+fn clear_frees_what_was_parked() {
+    let _serial = serial();
+    let alloc = Recording::default();
+
+    let ptr = calloc_with(1, cached(24), &alloc);
+    unsafe { free_with(ptr, &alloc) };
+    assert!(alloc.frees.borrow().is_empty());
+
+    let epoch = clear_epoch();
+    unsafe { clear_freelist_with(&alloc) };
+
+    assert_eq!(*alloc.frees.borrow(), vec![ptr as usize]);
+    assert_eq!(clear_epoch(), epoch + 1);
+}
+
+#[cfg(feature = "canary")]
+#[test]
+fn corrupted_blocks_are_replaced_and_freed_on_clear() {
+    let _serial = serial();
+    let alloc = Recording::default();
+
+    let size = cached(16).next_power_of_two();
+
+    let ptr = calloc_with(1, size, &alloc);
+    unsafe { free_with(ptr, &alloc) };
+
+    // Overrun past the bytes asked for
+    unsafe { (ptr as *mut u8).add(size).write(0xaa) };
+
+    let replacement = calloc_with(1, size, &alloc);
+    assert_ne!(replacement, ptr);
+    assert_eq!(alloc.callocs.borrow().len(), 2);
+
+    unsafe { free_with(replacement, &alloc) };
+    unsafe { clear_freelist_with(&alloc) };
+
+    let mut freed = alloc.frees.borrow().clone();
+    freed.sort_unstable();
+    let mut expected = vec![ptr as usize, replacement as usize];
+    expected.sort_unstable();
+    assert_eq!(freed, expected);
+}
+
+#[test]
+fn blocks_from_the_min_cache_size_up_are_reused() {
+    let _serial = serial();
+    let alloc = Recording::default();
+    let size = cached(3);
+
+    let ptr = calloc_with(1, size, &alloc);
+    unsafe { free_with(ptr, &alloc) };
+
+    assert_eq!(calloc_with(1, size, &alloc), ptr);
+    assert_eq!(*alloc.callocs.borrow(), vec![(1, size.next_power_of_two() + CANARY_PADDING)]);
+    assert!(alloc.frees.borrow().is_empty());
+}
+
+#[test]
+fn requests_below_the_min_cache_size_go_straight_through() {
+    if CALLOC_MIN_CACHE_SIZE < 2 {
+        return;
+    }
+
+    let _serial = serial();
+    let alloc = Recording::default();
+    let size = CALLOC_MIN_CACHE_SIZE - 1;
+
+    let ptr = calloc_with(1, size, &alloc);
+    unsafe { free_with(ptr, &alloc) };
+
+    assert_eq!(*alloc.callocs.borrow(), vec![(1, size)]);
+    assert_eq!(*alloc.frees.borrow(), vec![ptr as usize]);
+}
+
+#[test]
+fn cleared_blocks_are_forgotten() {
+    let _serial = serial();
+    let ptr = calloc_with(1, cached(48), &LibcAlloc);
+
+    unsafe { free_with(ptr, &LibcAlloc) };
+    unsafe { clear_freelist_with(&LibcAlloc) };
+
+    // Once the address is handed out again by anything else,
+    // freeing it must not park it in the freelist
+    let freed = RefCell::new(Vec::new());
+    free(ptr, |ptr| freed.borrow_mut().push(ptr));
+    assert_eq!(freed.into_inner(), vec![ptr]);
+}
+
+#[cfg(feature = "global-map")]
+#[test]
+fn cleared_blocks_are_forgotten_by_every_thread() {
+    let _serial = serial();
+    let ptr = calloc_with(1, cached(48), &LibcAlloc);
+
+    unsafe { free_with(ptr, &LibcAlloc) };
+    std::thread::spawn(|| unsafe { clear_freelist_with(&LibcAlloc) })
+        .join()
+        .unwrap();
+
+    let freed = RefCell::new(Vec::new());
+    free(ptr, |ptr| freed.borrow_mut().push(ptr));
+    assert_eq!(freed.into_inner(), vec![ptr]);
+}
+
+#[test]
+fn nested_calls_go_straight_to_the_underlying_allocator() {
+    let _serial = serial();
+    let alloc = Recording::default();
+    let inner = RefCell::new(None);
+
+    // Like an underlying allocator that itself allocates through calloc
+    let outer = calloc(1, cached(32), |nmemb, size| {
+        *inner.borrow_mut() = Some(calloc(1, 5, |nmemb, size| alloc.calloc(nmemb, size)));
+        LibcAlloc.calloc(nmemb, size)
+    });
+    let inner = inner.into_inner().unwrap();
+
+    // Neither rounded up nor tracked
+    assert_eq!(*alloc.callocs.borrow(), vec![(1, 5)]);
+
+    // Same for a free nested in the underlying free, even of a tracked pointer
+    free(inner, |ptr| unsafe {
+        free_with(outer, &alloc);
+        alloc.free(ptr)
+    });
+
+    assert_eq!(*alloc.frees.borrow(), vec![outer as usize, inner as usize]);
+}
+
+#[cfg(feature = "allocation-site")]
+#[test]
+fn allocation_site_is_where_calloc_was_called() {
+    use freelist::calloc::allocation_site;
+
+    let _serial = serial();
+    let size = cached(40);
+
+    let (ptr, line) = (calloc_with(1, size, &LibcAlloc), line!());
+    let site = allocation_site(ptr).unwrap();
+    assert_eq!((site.file(), site.line()), (file!(), line));
+
+    // Recycled blocks are attributed to their new caller
+    unsafe { free_with(ptr, &LibcAlloc) };
+    let (recycled, line) = (calloc_with(1, size, &LibcAlloc), line!());
+    assert_eq!(recycled, ptr);
+    assert_eq!(allocation_site(recycled).unwrap().line(), line);
+
+    let untracked = LibcAlloc.calloc(1, 8);
+    assert!(allocation_site(untracked).is_none());
+    unsafe { LibcAlloc.free(untracked) };
+}
+
+#[test]
+fn with_variants_go_through_the_underlying_alloc() {
+    let _serial = serial();
+    let alloc = Recording::default();
+    let size = cached(100);
+
+    let ptr = calloc_with(2, size, &alloc);
+    assert_eq!(
+        *alloc.callocs.borrow(),
+        vec![(1, (2 * size).next_power_of_two() + CANARY_PADDING)]
+    );
+
+    // Not from calloc, so it isn't parked
+    let untracked = LibcAlloc.calloc(1, 8);
+    unsafe { free_with(untracked, &alloc) };
+    assert_eq!(*alloc.frees.borrow(), vec![untracked as usize]);
+
+    unsafe { free_with(ptr, &alloc) };
+    unsafe { clear_freelist_with(&alloc) };
+    assert_eq!(*alloc.frees.borrow(), vec![untracked as usize, ptr as usize]);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn size_histogram_counts_each_call_under_its_power_of_2() {
+    use freelist::calloc::size_histogram;
+
+    let _serial = serial();
+    let count = |size| {
+        size_histogram()
+            .into_iter()
+            .find(|&(class, _)| class == size)
+            .map_or(0, |(_, count)| count)
+    };
+    let (before_512k, before_1m) = (count(1 << 19), count(1 << 20));
+
+    let ptrs = [
+        calloc(3, 100_000, |nmemb, size| LibcAlloc.calloc(nmemb, size)),
+        calloc(1, 1 << 19, |nmemb, size| LibcAlloc.calloc(nmemb, size)),
+        malloc(600_000, |size| unsafe { libc::malloc(size) }),
+    ];
+
+    // Overflowing requests fail before being counted
+    assert!(calloc(usize::MAX, 2, |_, _| unreachable!()).is_null());
+
+    assert_eq!(count(1 << 19), before_512k + 2);
+    assert_eq!(count(1 << 20), before_1m + 1);
+
+    let histogram = size_histogram();
+    assert!(histogram.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(histogram.iter().all(|&(size, count)| size.is_power_of_two() && count > 0));
+
+    for ptr in ptrs {
+        free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+    }
+}
+
+#[test]
+fn recycled_blocks_are_zeroed() {
+    let _serial = serial();
+    let alloc = Recording::default();
+    let size = cached(40);
+
+    let ptr = calloc_with(1, size, &alloc);
+    unsafe { ptr.cast::<u8>().write_bytes(0xab, size.next_power_of_two()) };
+    unsafe { free_with(ptr, &alloc) };
+
+    // Same total size, split differently, is served from the same bucket
+    let recycled = calloc_with(2, size / 2, &alloc);
+    assert_eq!(recycled, ptr);
+    assert_eq!(alloc.callocs.borrow().len(), 1);
+
+    let bytes = unsafe { std::slice::from_raw_parts(recycled.cast::<u8>(), size) };
+    assert!(bytes.iter().all(|&byte| byte == 0));
+
+    unsafe { free_with(recycled, &alloc) };
+}
+
+#[test]
+fn fresh_blocks_are_zeroed_by_the_underlying_calloc() {
+    let _serial = serial();
+    let size = cached(100);
+
+    let ptr = calloc(1, size, |nmemb, size| LibcAlloc.calloc(nmemb, size));
+    let bytes = unsafe { std::slice::from_raw_parts(ptr.cast::<u8>(), size) };
+    assert!(bytes.iter().all(|&byte| byte == 0));
+
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[test]
+fn overflowing_requests_return_null_without_allocating() {
+    let _serial = serial();
+    let alloc = Recording::default();
+
+    for (nmemb, size) in [
+        (usize::MAX, 2),
+        (2, usize::MAX / 2 + 1),
+        (1 << (usize::BITS / 2), 1 << (usize::BITS / 2)),
+    ] {
+        assert!(calloc(nmemb, size, |_, _| unreachable!()).is_null());
+        assert!(calloc_with(nmemb, size, &alloc).is_null());
+    }
+
+    assert!(alloc.callocs.borrow().is_empty());
+}
+
+#[test]
+fn products_that_just_fit_arent_overflows() {
+    let _serial = serial();
+    let alloc = Recording::default();
+    let size = cached(64);
+
+    let ptr = calloc_with(size / 8, 8, &alloc);
+    assert!(!ptr.is_null());
+    unsafe { free_with(ptr, &alloc) };
+
+    // Tracked under the product, so it comes back for the same total
+    assert_eq!(calloc_with(1, size, &alloc), ptr);
+    assert_eq!(alloc.callocs.borrow().len(), 1);
+}
+
+fn libc_realloc(ptr: *mut c_void, size: size_t) -> *mut c_void {
+    unsafe { libc::realloc(ptr, size) }
+}
+
+#[test]
+fn realloc_within_the_bucket_keeps_the_block() {
+    let _serial = serial();
+    let size = cached(40);
+
+    let ptr = calloc(1, size, |nmemb, size| LibcAlloc.calloc(nmemb, size));
+    let same = realloc(ptr, size.next_power_of_two(), |_, _| unreachable!(), |_| unreachable!());
+    assert_eq!(same, ptr);
+
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[test]
+fn realloc_to_another_bucket_copies_and_parks_the_old_block() {
+    let _serial = serial();
+    let size = cached(16);
+    let alloc = Recording::default();
+
+    let ptr = calloc_with(1, size, &alloc);
+    unsafe { std::slice::from_raw_parts_mut(ptr.cast::<u8>(), size) }.fill(9);
+
+    let bigger = realloc(ptr, size * 4, libc_realloc, |_| unreachable!());
+    assert!(!bigger.is_null());
+    assert_ne!(bigger, ptr);
+    assert!(unsafe { std::slice::from_raw_parts(bigger.cast::<u8>(), size) }
+        .iter()
+        .all(|&byte| byte == 9));
+
+    // The old block was parked instead of freed
+    assert_eq!(calloc_with(1, size, &alloc), ptr);
+    assert!(alloc.frees.borrow().is_empty());
+
+    unsafe { free_with(ptr, &alloc) };
+    free(bigger, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[test]
+fn realloc_of_null_allocates() {
+    let _serial = serial();
+    let size = cached(100);
+
+    let ptr = realloc(std::ptr::null_mut(), size, libc_realloc, |_| unreachable!());
+    assert!(!ptr.is_null());
+    free(ptr, |_| unreachable!());
+
+    // Tracked, so it was parked and comes back for the same size
+    assert_eq!(
+        realloc(std::ptr::null_mut(), size, |_, _| unreachable!(), |_| unreachable!()),
+        ptr
+    );
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[test]
+fn realloc_of_untracked_pointers_is_forwarded() {
+    let _serial = serial();
+    let untracked = LibcAlloc.calloc(1, 16);
+    let forwarded = RefCell::new(Vec::new());
+
+    let ptr = realloc(
+        untracked,
+        cached(32),
+        |ptr, size| {
+            forwarded.borrow_mut().push((ptr as usize, size));
+            libc_realloc(ptr, size)
+        },
+        |_| unreachable!(),
+    );
+
+    assert_eq!(*forwarded.borrow(), vec![(untracked as usize, cached(32))]);
+    unsafe { libc::free(ptr) };
+}
+
+#[test]
+fn malloc_allocates_the_bucket_size() {
+    let _serial = serial();
+    let size = cached(50);
+    let requested = RefCell::new(Vec::new());
+
+    let ptr = malloc(size, |size| {
+        requested.borrow_mut().push(size);
+        unsafe { libc::malloc(size) }
+    });
+
+    assert_eq!(*requested.borrow(), vec![size.next_power_of_two() + CANARY_PADDING]);
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[test]
+fn malloc_doesnt_zero_recycled_blocks() {
+    let _serial = serial();
+    let size = cached(50);
+
+    let ptr = malloc(size, |size| unsafe { libc::malloc(size) });
+    unsafe { ptr.cast::<u8>().write_bytes(0x5a, size) };
+    free(ptr, |_| unreachable!());
+
+    let recycled = malloc(size, |_| unreachable!());
+    assert_eq!(recycled, ptr);
+    assert!(unsafe { std::slice::from_raw_parts(recycled.cast::<u8>(), size) }
+        .iter()
+        .all(|&byte| byte == 0x5a));
+
+    // Blocks are shared with calloc, which does zero them
+    free(recycled, |_| unreachable!());
+    assert_eq!(calloc(1, size, |_, _| unreachable!()), ptr);
+    assert!(unsafe { std::slice::from_raw_parts(ptr.cast::<u8>(), size) }
+        .iter()
+        .all(|&byte| byte == 0));
+
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
+
+#[cfg(feature = "c-api")]
+#[test]
+fn c_api_round_trips_through_the_freelist() {
+    use freelist::calloc::{freelist_calloc, freelist_free, mbedtls_calloc_free};
+
+    let _serial = serial();
+    let size = cached(24);
+
+    // Called through function pointers, like mbedtls does
+    let (mbedtls_calloc, mbedtls_free) = mbedtls_calloc_free();
+
+    unsafe {
+        let ptr = mbedtls_calloc(2, size);
+        assert!(!ptr.is_null());
+        ptr.cast::<u8>().write_bytes(1, 2 * size);
+        mbedtls_free(ptr);
+
+        let recycled = mbedtls_calloc(1, 2 * size);
+        assert_eq!(recycled, ptr);
+        assert!(std::slice::from_raw_parts(recycled.cast::<u8>(), 2 * size)
+            .iter()
+            .all(|&byte| byte == 0));
+
+        mbedtls_free(recycled);
+        mbedtls_free(std::ptr::null_mut());
+        assert!(mbedtls_calloc(usize::MAX, 2).is_null());
+
+        // Same wrappers when called directly
+        let ptr = freelist_calloc(1, size);
+        freelist_free(ptr);
+        assert_eq!(freelist_calloc(1, size), ptr);
+        freelist_free(ptr);
+    }
+}
+
+fn libc_aligned_calloc(size: size_t, align: size_t) -> *mut c_void {
+    unsafe {
+        let ptr = libc::aligned_alloc(align, (size + align - 1) / align * align);
+        ptr.cast::<u8>().write_bytes(0, size);
+        ptr
+    }
+}
+
+#[test]
+fn aligned_calloc_returns_aligned_blocks() {
+    let _serial = serial();
+    let size = cached(48);
+
+    let ptr = aligned_calloc(1, size, 128, libc_aligned_calloc);
+    assert_eq!(ptr as usize % 128, 0);
+    free(ptr, |_| unreachable!());
+
+    assert_eq!(aligned_calloc(1, size, 128, |_, _| unreachable!()), ptr);
+    free(ptr, |ptr| unsafe { LibcAlloc.free(ptr) });
+
+    assert!(aligned_calloc(1, size, 48, |_, _| unreachable!()).is_null());
+    assert!(aligned_calloc(usize::MAX, 2, 8, |_, _| unreachable!()).is_null());
+}
+
+#[test]
+fn aligned_calloc_skips_misaligned_blocks() {
+    let _serial = serial();
+    let size = cached(64);
+
+    // A block from calloc that is 8 but not 32 byte aligned, carved out
+    // of leaked memory, so it must never reach the underlying free
+    let region = Box::leak(vec![0u8; 4 * size].into_boxed_slice()).as_mut_ptr();
+    let misaligned = region.wrapping_add(region.align_offset(32) + 8).cast::<c_void>();
+
+    assert_eq!(calloc(1, size, |_, _| misaligned), misaligned);
+    free(misaligned, |_| unreachable!());
+
+    let aligned = aligned_calloc(1, size, 32, libc_aligned_calloc);
+    assert_ne!(aligned, misaligned);
+    assert_eq!(aligned as usize % 32, 0);
+
+    // Still parked for requests that don't need the alignment
+    assert_eq!(calloc(1, size, |_, _| unreachable!()), misaligned);
+    free(aligned, |ptr| unsafe { LibcAlloc.free(ptr) });
+}
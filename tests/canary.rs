@@ -0,0 +1,80 @@
+#![cfg(feature = "canary")]
+
+mod common;
+
+use std::cell::Cell;
+
+use common::block;
+use freelist::{Error, FreeList, CANARY_PADDING};
+
+#[test]
+fn canary_needs_a_usize_past_the_block() {
+    assert_eq!(CANARY_PADDING, core::mem::size_of::<usize>());
+}
+
+#[test]
+fn overrun_is_reported_with_the_address() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(16);
+
+    list.throw(ptr, 16).unwrap();
+    unsafe { ptr.add(16).write(0xaa) };
+
+    assert!(matches!(list.recycle(16), Err(Error::Corrupted(addr)) if addr == ptr as usize));
+
+    // Taken out, so it's the caller's to free
+    assert!(list.is_empty());
+    assert!(matches!(list.recycle(16), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn get_or_alloc_frees_corrupted_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let (ptr, fresh) = (block(16), block(16));
+    let freed = Cell::new(None);
+
+    list.throw(ptr, 16).unwrap();
+    unsafe { ptr.add(16).write(0xaa) };
+
+    assert_eq!(list.get_or_alloc(16, |_| fresh, |ptr| freed.set(Some(ptr))), (fresh, 16));
+    assert_eq!(freed.get(), Some(ptr));
+    assert!(list.is_empty());
+}
+
+#[test]
+fn checkout_frees_corrupted_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let (ptr, fresh) = (block(16), block(16));
+    let freed = Cell::new(None);
+
+    list.throw(ptr, 16).unwrap();
+    unsafe { ptr.add(16).write(0xaa) };
+
+    let guard = list.checkout(16, |_| fresh, |ptr| freed.set(Some(ptr)));
+    assert_eq!(guard.as_ptr(), fresh);
+    assert_eq!(freed.get(), Some(ptr));
+
+    // The fresh block is parked, not freed
+    drop(guard);
+    assert_eq!(freed.get(), Some(ptr));
+    assert_eq!(list.recycle(16).unwrap(), fresh);
+}
+
+#[test]
+fn writes_within_the_block_are_fine() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(16);
+
+    list.throw(ptr, 16).unwrap();
+    unsafe { ptr.write_bytes(0xff, 16) };
+
+    assert_eq!(list.recycle(16).unwrap(), ptr);
+}
+
+#[test]
+fn corrupted_is_displayed_with_the_address() {
+    assert_eq!(
+        Error::Corrupted(0x1000).to_string(),
+        "canary past the block at 0x1000 was overwritten"
+    );
+}
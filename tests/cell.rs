@@ -0,0 +1,96 @@
+#![cfg(feature = "hot-swap")]
+
+mod common;
+
+use std::{
+    cell::RefCell,
+    sync::{Arc, Barrier, Mutex},
+    thread,
+};
+
+use common::{block, blocks};
+use freelist::{Error, FreeList, FreeListCell};
+
+#[test]
+fn loads_after_a_swap_see_the_new_freelist() {
+    let cell = FreeListCell::new(FreeList::<u8, 4>::new());
+    let ptr = block(4);
+    cell.load().throw(ptr, 4).unwrap();
+
+    let freed = Mutex::new(Vec::new());
+    let old = cell.swap(FreeList::new(), |ptr, power| freed.lock().unwrap().push((ptr, power)));
+
+    assert_eq!(*freed.lock().unwrap(), vec![(ptr, 2)]);
+    assert!(cell.load().is_empty());
+    assert!(!Arc::ptr_eq(&old, &cell.load()));
+
+    cell.load().throw(block(8), 8).unwrap();
+    assert_eq!(cell.load().len(), 1);
+}
+
+#[test]
+fn holders_of_the_old_freelist_get_sealed() {
+    let cell = FreeListCell::new(FreeList::<u8, 4>::new());
+    let held = cell.load();
+
+    cell.swap(FreeList::new(), |_, _| unreachable!());
+
+    assert!(matches!(held.throw(block(4), 4), Err(Error::Sealed)));
+    assert!(matches!(held.recycle(4), Err(Error::Sealed)));
+    cell.load().throw(block(4), 4).unwrap();
+}
+
+#[test]
+fn in_flight_operations_on_the_old_freelist_finish_safely() {
+    const PER_THREAD: usize = 500;
+
+    let cell = Arc::new(FreeListCell::new(FreeList::<u8, 4>::new()));
+    let start = Arc::new(Barrier::new(5));
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let (cell, start) = (Arc::clone(&cell), Arc::clone(&start));
+            let ptrs: Vec<usize> = blocks(PER_THREAD, 8).into_iter().map(|ptr| ptr as usize).collect();
+
+            thread::spawn(move || {
+                start.wait();
+
+                // Blocks the freelist doesn't take are freed by the thread itself
+                let mut freed = Vec::new();
+                for ptr in ptrs {
+                    let list = cell.load();
+
+                    if list.throw(ptr as *mut u8, 8).is_err() {
+                        freed.push(ptr);
+                    }
+                    if let Ok(ptr) = list.recycle(8) {
+                        freed.push(ptr as usize);
+                    }
+                }
+                freed
+            })
+        })
+        .collect();
+
+    start.wait();
+    let drained = Mutex::new(Vec::new());
+    let old = cell.swap(FreeList::new(), |ptr, _| drained.lock().unwrap().push(ptr as usize));
+
+    let mut seen: Vec<usize> = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+    seen.extend(drained.into_inner().unwrap());
+
+    // Anything parked in the old freelist after the drain is cleared once it's the last user
+    let old = Arc::try_unwrap(old).ok().unwrap();
+    let seen = RefCell::new(seen);
+    old.clear(|ptr, _| seen.borrow_mut().push(ptr as usize));
+
+    let mut seen = seen.into_inner();
+    while let Ok(ptr) = cell.load().recycle(8) {
+        seen.push(ptr as usize);
+    }
+
+    // Every block came out exactly once, from either freelist or a failed throw
+    seen.sort_unstable();
+    seen.dedup();
+    assert_eq!(seen.len(), 4 * PER_THREAD);
+}
@@ -0,0 +1,39 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use freelist::CANARY_PADDING;
+
+/// Returns a block that can be thrown with `size`, i.e., with room
+/// for the canary of `canary` feature. Blocks are leaked, as they
+/// only live as long as the test.
+pub fn block(size: usize) -> *mut u8 {
+    Box::leak(vec![0u8; size + CANARY_PADDING].into_boxed_slice()).as_mut_ptr()
+}
+
+/// Returns `len` bytes of zeroed memory, e.g. to carve blocks
+/// out of. Leaked like [block].
+pub fn region(len: usize) -> *mut u8 {
+    Box::leak(vec![0u8; len].into_boxed_slice()).as_mut_ptr()
+}
+
+/// Returns `count` blocks for `size` carved out of a single region,
+/// each one right after the previous one and its canary.
+pub fn adjacent_blocks(count: usize, size: usize) -> Vec<*mut u8> {
+    let base = region(count * (size + CANARY_PADDING));
+
+    (0..count).map(|i| base.wrapping_add(i * (size + CANARY_PADDING))).collect()
+}
+
+/// Returns `count` distinct blocks for `size`, sorted by address.
+pub fn blocks(count: usize, size: usize) -> Vec<*mut u8> {
+    let mut blocks: Vec<_> = (0..count).map(|_| block(size)).collect();
+    blocks.sort_unstable();
+    blocks
+}
+
+/// Returns `ptrs` sorted, for comparing sets of pointers.
+pub fn sorted<T>(mut ptrs: Vec<*mut T>) -> Vec<*mut T> {
+    ptrs.sort_unstable();
+    ptrs
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "debug-checks")]
+
+mod common;
+
+use common::{block, blocks};
+use freelist::{Dump, Error, FreeList};
+
+#[test]
+fn throwing_a_parked_pointer_again_is_rejected() {
+    let list = FreeList::<u8, 4>::new();
+    let ptr = block(4);
+
+    list.throw(ptr, 4).unwrap();
+    assert!(matches!(list.throw(ptr, 4), Err(Error::DoubleThrow)));
+    assert!(matches!(list.throw_priority(ptr, 4), Err(Error::DoubleThrow)));
+
+    assert_eq!(list.bucket_len(4).unwrap(), 1);
+    assert_eq!(list.total_bytes_held(), 4);
+
+    // Once recycled, it's no longer parked and can be thrown again
+    assert_eq!(list.recycle(4).unwrap(), ptr);
+    list.throw(ptr, 4).unwrap();
+}
+
+#[test]
+fn double_throws_into_a_full_bucket_are_told_apart() {
+    let list = FreeList::<u8, 4>::new();
+    let ptrs = blocks(list.free_slots_for(2).unwrap(), 2);
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 2).unwrap());
+
+    assert!(matches!(list.throw(ptrs[3], 2), Err(Error::DoubleThrow)));
+    assert!(matches!(list.throw(block(2), 2), Err(Error::BucketFull)));
+    assert_eq!(Error::DoubleThrow.to_string(), "pointer is already in the bucket");
+}
+
+#[test]
+fn put_or_free_doesnt_free_a_parked_pointer() {
+    let list = FreeList::<u8, 4>::new();
+    let ptr = block(4);
+
+    assert!(list.put_or_free(ptr, 3, |_| unreachable!()));
+    assert!(list.put_or_free(ptr, 3, |_| panic!("freed while parked")));
+
+    assert_eq!(list.bucket_len(4).unwrap(), 1);
+    assert_eq!(list.recycle(4).unwrap(), ptr);
+}
+
+#[test]
+fn dump_rejects_values_it_already_holds() {
+    let dump = Dump::<u8>::new();
+    let ptrs = blocks(3, 1);
+
+    ptrs.iter().for_each(|&ptr| dump.throw(ptr).unwrap());
+
+    for &ptr in &ptrs {
+        assert_eq!(dump.throw(ptr), Err(ptr));
+        assert_eq!(dump.throw_priority(ptr), Err(ptr));
+    }
+    assert_eq!(dump.len(), 3);
+}
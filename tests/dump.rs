@@ -0,0 +1,225 @@
+mod common;
+
+use std::{cell::RefCell, sync::Arc, thread};
+
+use common::{block, blocks, sorted};
+use freelist::{Dump, FreeList, SpmcDump};
+
+#[test]
+fn concurrent_throws_and_recycles_lose_nothing() {
+    let dump = Arc::new(Dump::<u8>::new());
+    let per_thread = Dump::<u8>::capacity() / 4;
+    let ptrs: Vec<usize> = blocks(4 * per_thread, 8).into_iter().map(|ptr| ptr as usize).collect();
+
+    // Each thread throws its share and recycles half of what it threw
+    let handles: Vec<_> = ptrs
+        .chunks(per_thread)
+        .map(|chunk| {
+            let dump = Arc::clone(&dump);
+            let chunk = chunk.to_vec();
+
+            thread::spawn(move || {
+                let mut recycled = Vec::new();
+
+                for (i, &addr) in chunk.iter().enumerate() {
+                    dump.throw(addr as *mut u8).unwrap();
+
+                    if i % 2 == 1 {
+                        recycled.push(dump.recycle().unwrap() as usize);
+                    }
+                }
+
+                recycled
+            })
+        })
+        .collect();
+
+    let mut seen: Vec<usize> = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+
+    while let Some(ptr) = dump.recycle() {
+        seen.push(ptr as usize);
+    }
+
+    seen.sort_unstable();
+    assert_eq!(seen, ptrs);
+    assert!(dump.is_empty());
+}
+
+#[test]
+fn throw_hands_the_pointer_back_when_full() {
+    let dump = Dump::<u8>::new();
+
+    for ptr in blocks(Dump::<u8>::capacity(), 1) {
+        dump.throw(ptr).unwrap();
+    }
+
+    let extra = block(1);
+    assert_eq!(dump.throw(extra), Err(extra));
+    assert_eq!(dump.len(), Dump::<u8>::capacity());
+}
+
+#[test]
+fn recycle_of_an_empty_dump_is_none() {
+    let dump = Dump::<u8>::new();
+
+    assert_eq!(dump.recycle(), None);
+
+    let ptr = block(1);
+    dump.throw(ptr).unwrap();
+    assert_eq!(dump.recycle(), Some(ptr));
+    assert_eq!(dump.recycle(), None);
+}
+
+#[test]
+fn clear_hands_out_every_value_once() {
+    let dump = Dump::<u8>::new();
+    let ptrs = blocks(5, 4);
+    let cleared = RefCell::new(Vec::new());
+
+    ptrs.iter().for_each(|&ptr| dump.throw(ptr).unwrap());
+    dump.clear(|ptr| cleared.borrow_mut().push(ptr));
+
+    assert_eq!(sorted(cleared.into_inner()), ptrs);
+    assert!(dump.is_empty());
+}
+
+#[test]
+fn bitmap_snapshots_track_the_occupied_spots() {
+    let dump = Dump::<u8>::new();
+
+    assert_eq!((dump.reader_bitmap_snapshot(), dump.writer_bitmap_snapshot()), (0, 0));
+
+    blocks(3, 1).into_iter().for_each(|ptr| dump.throw(ptr).unwrap());
+    assert_eq!((dump.reader_bitmap_snapshot(), dump.writer_bitmap_snapshot()), (0b111, 0b111));
+
+    // The first set spot is recycled, and the next throw fills it again
+    dump.recycle().unwrap();
+    assert_eq!((dump.reader_bitmap_snapshot(), dump.writer_bitmap_snapshot()), (0b110, 0b110));
+
+    dump.throw(block(1)).unwrap();
+    assert_eq!(dump.reader_bitmap_snapshot(), 0b111);
+}
+
+#[test]
+fn snapshot_of_an_empty_dump_is_empty() {
+    let dump = Dump::<u8>::new();
+
+    unsafe { dump.snapshot(|ptrs| assert!(ptrs.is_empty())) };
+
+    let ptr = block(1);
+    dump.throw(ptr).unwrap();
+    unsafe { dump.snapshot(|ptrs| assert_eq!(ptrs, [ptr])) };
+    assert_eq!(dump.len(), 1);
+}
+
+#[test]
+fn is_empty_and_is_full_through_empty_partial_and_full() {
+    let dump = Dump::<u8>::new();
+    let ptrs = blocks(Dump::<u8>::capacity(), 1);
+
+    assert!(dump.is_empty());
+    assert!(!dump.is_full());
+
+    dump.throw(ptrs[0]).unwrap();
+    assert!(!dump.is_empty());
+    assert!(!dump.is_full());
+
+    ptrs[1..].iter().for_each(|&ptr| dump.throw(ptr).unwrap());
+    assert!(!dump.is_empty());
+    assert!(dump.is_full());
+
+    while dump.recycle().is_some() {}
+    assert!(dump.is_empty());
+    assert!(!dump.is_full());
+}
+
+#[test]
+fn dumps_are_cache_line_aligned() {
+    use std::mem::{align_of, size_of};
+
+    assert_eq!(align_of::<Dump<u8>>(), 64);
+    assert_eq!(size_of::<Dump<u8>>() % 64, 0);
+
+    // Each bitmap is on a line of its own, before the values
+    assert!(size_of::<Dump<u8, 1, u8>>() >= 3 * 64);
+
+    // So neighbouring dumps of a freelist never share a line either
+    assert_eq!(align_of::<FreeList<u8, 4>>(), 64);
+    assert!(size_of::<FreeList<u8, 4>>() >= 4 * size_of::<Dump<u8>>());
+}
+
+#[test]
+fn dumps_in_an_array_start_on_their_own_line() {
+    let dumps: [Dump<u8>; 3] = [Dump::new(), Dump::new(), Dump::new()];
+
+    for dump in &dumps {
+        assert_eq!(dump as *const Dump<u8> as usize % 64, 0);
+    }
+}
+
+#[test]
+fn debug_counts_the_bits_set_in_each_bitmap() {
+    let dump = Dump::<u8>::default();
+    assert_eq!(format!("{:?}", dump), "Dump { reader_bits: 0, writer_bits: 0 }");
+
+    for ptr in blocks(3, 1) {
+        dump.throw(ptr).unwrap();
+    }
+    dump.recycle().unwrap();
+
+    assert_eq!(format!("{:?}", dump), "Dump { reader_bits: 2, writer_bits: 2 }");
+}
+
+#[test]
+fn spmc_dumps_default_to_empty() {
+    let dump = SpmcDump::<u8>::default();
+    let ptr = block(1);
+
+    // Only this thread recycles
+    unsafe {
+        assert_eq!(dump.recycle(), None);
+        dump.throw(ptr).unwrap();
+        assert_eq!(dump.recycle(), Some(ptr));
+    }
+}
+
+#[test]
+fn spmc_dumps_hand_out_every_throw_once() {
+    let dump = Arc::new(SpmcDump::<u8>::new());
+    let per_thread = 4 * Dump::<u8>::capacity();
+    let ptrs: Vec<usize> = blocks(4 * per_thread, 8).into_iter().map(|ptr| ptr as usize).collect();
+
+    // Producers throw more than fits, so they retry until the consumer makes room
+    let handles: Vec<_> = ptrs
+        .chunks(per_thread)
+        .map(|chunk| {
+            let dump = Arc::clone(&dump);
+            let chunk = chunk.to_vec();
+
+            thread::spawn(move || {
+                for addr in chunk {
+                    while dump.throw(addr as *mut u8).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            })
+        })
+        .collect();
+
+    // This is the only thread that recycles
+    let mut seen = Vec::new();
+    while seen.len() < ptrs.len() {
+        match unsafe { dump.recycle() } {
+            Some(ptr) => seen.push(ptr as usize),
+            None => thread::yield_now(),
+        }
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    seen.sort_unstable();
+    assert_eq!(seen, ptrs);
+    assert_eq!(unsafe { dump.recycle() }, None);
+}
@@ -0,0 +1,34 @@
+#![cfg(any(feature = "std", feature = "core-error"))]
+
+use freelist::FreeList;
+
+#[cfg(feature = "std")]
+#[test]
+fn errors_convert_into_boxed_std_errors() {
+    fn recycle_from_empty() -> Result<*mut u8, Box<dyn std::error::Error>> {
+        Ok(FreeList::<u8, 4>::new().recycle(4)?)
+    }
+
+    let err = recycle_from_empty().unwrap_err();
+
+    assert_eq!(err.to_string(), "bucket is empty");
+    assert!(err.source().is_none());
+    assert!(matches!(
+        err.downcast_ref::<freelist::Error>(),
+        Some(freelist::Error::BucketEmpty)
+    ));
+}
+
+#[cfg(all(feature = "core-error", not(feature = "std")))]
+#[test]
+#[allow(clippy::incompatible_msrv)] // core-error needs rust 1.81 anyway
+fn errors_are_core_errors_without_std() {
+    fn describe(err: &dyn core::error::Error) -> String {
+        err.to_string()
+    }
+
+    let err = FreeList::<u8, 4>::new().recycle(3).unwrap_err();
+
+    assert_eq!(describe(&err), "given size should be power of 2");
+    assert!(core::error::Error::source(&err).is_none());
+}
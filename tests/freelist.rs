@@ -0,0 +1,879 @@
+mod common;
+
+use std::{
+    alloc::{self, Layout},
+    cell::RefCell,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::Ordering,
+};
+
+use common::{adjacent_blocks, block, blocks, region, sorted};
+use freelist::{Dump, Error, FreeList, CANARY_PADDING};
+
+#[cfg(feature = "alloc")]
+#[test]
+fn take_bucket_returns_every_pointer_once() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(5, 16);
+
+    for &ptr in &ptrs {
+        list.throw(ptr, 16).unwrap();
+    }
+
+    assert_eq!(sorted(list.take_bucket(16).unwrap()), ptrs);
+    assert_eq!(list.bucket_len(16).unwrap(), 0);
+    assert!(list.take_bucket(16).unwrap().is_empty());
+    assert!(matches!(list.recycle(16), Err(Error::BucketEmpty)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn take_bucket_leaves_other_buckets_alone() {
+    let list = FreeList::<u8, 8>::new();
+    let small = blocks(2, 8);
+    let big = blocks(3, 32);
+
+    small.iter().for_each(|&ptr| list.throw(ptr, 8).unwrap());
+    big.iter().for_each(|&ptr| list.throw(ptr, 32).unwrap());
+
+    assert_eq!(sorted(list.take_bucket(32).unwrap()), big);
+    assert_eq!(list.bucket_len(8).unwrap(), 2);
+    assert!(matches!(list.take_bucket(12), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.take_bucket(256), Err(Error::BucketNotAvailable)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn prefill_all_fills_every_bucket() {
+    let list = FreeList::<u8, 4>::new();
+    let mut allocated = Vec::new();
+
+    let counts = list.prefill_all(
+        3,
+        |size| {
+            let ptr = block(size);
+            allocated.push(ptr);
+            ptr
+        },
+        |_, _| unreachable!(),
+    );
+
+    assert_eq!(counts, vec![3, 3, 3, 3]);
+
+    let parked = [1, 2, 4, 8]
+        .iter()
+        .flat_map(|&size| list.take_bucket(size).unwrap())
+        .collect();
+    assert_eq!(sorted(parked), sorted(allocated));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn prefill_all_stops_a_bucket_at_null() {
+    let list = FreeList::<u8, 3>::new();
+    let mut calls = 0;
+
+    let counts = list.prefill_all(
+        4,
+        |size| {
+            calls += 1;
+
+            // The bucket for 2 bytes gets a single block
+            if size == 2 && calls > 5 {
+                ptr::null_mut()
+            } else {
+                block(size)
+            }
+        },
+        |_, _| unreachable!(),
+    );
+
+    assert_eq!(counts, vec![4, 1, 4]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn prefill_all_frees_what_it_cant_throw() {
+    let list = FreeList::<u8, 4>::new();
+    let freed = RefCell::new(Vec::new());
+
+    // 4 blocks of 1 byte, 4 of 2 bytes and a single one of 4 bytes
+    list.set_max_bytes(16);
+
+    let counts = list.prefill_all(4, block, |ptr, size| freed.borrow_mut().push((ptr, size)));
+
+    assert_eq!(counts, vec![4, 4, 1, 0]);
+    assert_eq!(list.total_bytes_held(), 16);
+
+    // Every bucket that didn't fit stopped at its first such block
+    let freed: Vec<usize> = freed.into_inner().into_iter().map(|(_, size)| size).collect();
+    assert_eq!(freed, vec![4, 8]);
+}
+
+// Checked at compile time, for whatever the target is
+const _: () = assert!(FreeList::<u8, 11>::TOTAL_CAPACITY == 11 * usize::BITS as usize);
+
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(FreeList::<u8, 11>::TOTAL_CAPACITY == 704);
+
+#[test]
+fn capacity_is_total_capacity() {
+    let list = FreeList::<u8, 11>::new();
+
+    assert_eq!(list.capacity(), FreeList::<u8, 11>::TOTAL_CAPACITY);
+    assert_eq!(list.free_slots(), list.capacity());
+}
+
+/// Whether `a` and `b` are right next to each other, with room for
+/// the canary of the lower one.
+fn adjacent(size: usize) -> impl Fn(*mut u8, *mut u8) -> bool {
+    move |a, b| a.max(b) as usize - a.min(b) as usize == size + CANARY_PADDING
+}
+
+#[test]
+fn try_coalesce_merges_adjacent_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = adjacent_blocks(4, 16);
+
+    // Throw them out of order so that pairs have to be looked for
+    for &i in &[2, 0, 3, 1] {
+        list.throw(ptrs[i], 16).unwrap();
+    }
+
+    // The middle two are adjacent as well, so the predicate also
+    // checks which block comes first in a pair
+    let base = ptrs[0] as usize;
+    let pair_size = 2 * (16 + CANARY_PADDING);
+    let buddies = |a: *mut u8, b: *mut u8| adjacent(16)(a, b) && (a.min(b) as usize - base) % pair_size == 0;
+
+    assert_eq!(list.try_coalesce(16, buddies).unwrap(), 2);
+    assert_eq!(list.bucket_len(16).unwrap(), 0);
+
+    // Merged blocks pass the checks of the bigger bucket
+    assert_eq!(
+        sorted(vec![list.recycle(32).unwrap(), list.recycle(32).unwrap()]),
+        vec![ptrs[0], ptrs[2]]
+    );
+}
+
+#[test]
+fn try_coalesce_leaves_blocks_that_arent_buddies() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = adjacent_blocks(3, 16);
+
+    list.throw(ptrs[0], 16).unwrap();
+    list.throw(ptrs[2], 16).unwrap();
+
+    assert_eq!(list.try_coalesce(16, adjacent(16)).unwrap(), 0);
+    assert_eq!(
+        sorted(vec![list.recycle(16).unwrap(), list.recycle(16).unwrap()]),
+        vec![ptrs[0], ptrs[2]]
+    );
+    assert_eq!(list.bucket_len(32).unwrap(), 0);
+}
+
+#[test]
+fn try_coalesce_keeps_room_for_the_canary() {
+    let list = FreeList::<u8, 8>::new();
+    let base = region(3 * 16 + CANARY_PADDING);
+
+    // Only adjacent if there's no canary in between
+    list.throw(base, 16).unwrap();
+    list.throw(base.wrapping_add(16), 16).unwrap();
+
+    let merges = list.try_coalesce(16, |_, _| true).unwrap();
+
+    assert_eq!(merges, if CANARY_PADDING == 0 { 1 } else { 0 });
+}
+
+#[test]
+fn try_coalesce_needs_a_bucket_above() {
+    let list = FreeList::<u8, 8>::new();
+
+    assert!(matches!(list.try_coalesce(128, |_, _| true), Err(Error::BucketNotAvailable)));
+    assert!(matches!(list.try_coalesce(24, |_, _| true), Err(Error::SizeNotPowerOf2)));
+}
+
+#[test]
+fn recycle_by_splitting_parks_the_upper_half() {
+    let list = FreeList::<u8, 8>::new();
+    let halves = adjacent_blocks(2, 16);
+
+    list.throw(halves[0], 32).unwrap();
+
+    assert_eq!(list.recycle_by_splitting(16).unwrap(), (halves[0], 16));
+    assert_eq!(list.bucket_len(32).unwrap(), 0);
+
+    // The upper half passes the checks of its bucket, and is
+    // what the next split recycles without splitting anything
+    assert_eq!(list.recycle_by_splitting(16).unwrap(), (halves[1], 16));
+    assert!(matches!(list.recycle_by_splitting(16), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn recycle_by_splitting_returns_the_whole_block_if_the_half_doesnt_fit() {
+    let list = FreeList::<u8, 8>::new();
+    let halves = adjacent_blocks(2, 16);
+
+    list.throw(halves[0], 32).unwrap();
+    list.set_max_bytes(8);
+
+    assert_eq!(list.recycle_by_splitting(16).unwrap(), (halves[0], 32));
+    assert!(list.is_empty());
+}
+
+#[test]
+fn debug_shows_the_occupancy_of_every_bucket() {
+    let list = FreeList::<u8, 3>::new();
+
+    blocks(3, 2).into_iter().for_each(|ptr| list.throw(ptr, 2).unwrap());
+
+    assert_eq!(
+        format!("{:?}", list),
+        format!(
+            "FreeList {{bucket[1B]: 0/{c}, bucket[2B]: 3/{c}, bucket[4B]: 0/{c}}}",
+            c = usize::BITS
+        ),
+    );
+}
+
+#[test]
+fn debug_pretty_prints_a_bucket_per_line() {
+    let list = FreeList::<u8, 2>::new();
+
+    list.throw(block(1), 1).unwrap();
+
+    assert_eq!(
+        format!("{:#?}", list),
+        format!(
+            "FreeList {{\n    bucket[1B]: 1/{c},\n    bucket[2B]: 0/{c},\n}}",
+            c = usize::BITS
+        ),
+    );
+}
+
+#[test]
+fn remap_relocates_every_parked_pointer() {
+    let mut list = FreeList::<u8, 8>::new();
+    let moved = RefCell::new(Vec::new());
+
+    blocks(3, 16).into_iter().for_each(|ptr| list.throw(ptr, 16).unwrap());
+    list.throw(block(4), 4).unwrap();
+
+    // Copies the canary along, like compacting an arena would
+    list.remap(|ptr, power| {
+        let size = 1 << power;
+        let new = block(size);
+        unsafe { ptr::copy_nonoverlapping(ptr, new, size + CANARY_PADDING) };
+        moved.borrow_mut().push((size, new));
+        new
+    });
+
+    let moved = moved.into_inner();
+    assert_eq!(moved.iter().filter(|&&(size, _)| size == 4).count(), 1);
+
+    let recycled = sorted((0..3).map(|_| list.recycle(16).unwrap()).collect());
+    let expected = sorted(moved.iter().filter(|&&(size, _)| size == 16).map(|&(_, ptr)| ptr).collect());
+    assert_eq!(recycled, expected);
+    assert!(matches!(list.recycle(16), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn dump_remap_replaces_values_in_place() {
+    let mut dump = Dump::<u8>::new();
+    let ptrs = blocks(2, 8);
+
+    ptrs.iter().for_each(|&ptr| dump.throw(ptr).unwrap());
+    dump.remap(|ptr| ptr.wrapping_add(1));
+
+    let recycled = sorted(vec![dump.recycle().unwrap(), dump.recycle().unwrap()]);
+    assert_eq!(recycled, vec![ptrs[0].wrapping_add(1), ptrs[1].wrapping_add(1)]);
+    assert!(dump.is_empty());
+}
+
+#[test]
+fn validate_sizes_accepts_every_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(list.validate_sizes(&[1, 2, 4, 8, 8, 1]).is_ok());
+    assert!(list.validate_sizes(&[]).is_ok());
+}
+
+#[test]
+fn validate_sizes_points_at_the_first_bad_size() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.validate_sizes(&[1, 6, 16]), Err((1, Error::SizeNotPowerOf2))));
+    assert!(matches!(
+        list.validate_sizes(&[1, 16, 6]),
+        Err((1, Error::BucketNotAvailable))
+    ));
+    assert!(matches!(list.validate_sizes(&[4, 0]), Err((1, Error::SizeNotPowerOf2))));
+}
+
+#[test]
+fn drain_and_seal_frees_everything_and_rejects_the_rest() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(3, 4);
+    let freed = RefCell::new(Vec::new());
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 4).unwrap());
+    assert!(!list.is_sealed());
+
+    list.drain_and_seal(|ptr, power| freed.borrow_mut().push((ptr, power)));
+
+    assert!(list.is_sealed());
+    assert!(list.is_empty());
+    assert_eq!(freed.into_inner().into_iter().filter(|&(_, power)| power == 2).count(), 3);
+
+    let ptr = block(4);
+    assert!(matches!(list.throw(ptr, 4), Err(Error::Sealed)));
+    assert!(matches!(list.throw_priority(ptr, 4), Err(Error::Sealed)));
+    assert!(matches!(list.recycle(4), Err(Error::Sealed)));
+    assert!(matches!(list.try_recycle_once(4), Err(Error::Sealed)));
+    assert!(matches!(list.recycle_within(4, 2), Err(Error::Sealed)));
+    assert!(matches!(list.recycle_with_slot(4), Err(Error::Sealed)));
+    assert!(matches!(list.recycle_if_above(4, 0), Err(Error::Sealed)));
+    assert!(matches!(list.recycle_aligned(4, 4), Err(Error::Sealed)));
+    assert!(matches!(list.try_coalesce(4, |_, _| true), Err(Error::Sealed)));
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn sealed_is_checked_before_the_size() {
+    let list = FreeList::<u8, 2>::new();
+
+    list.drain_and_seal(|_, _| unreachable!());
+
+    assert!(matches!(list.throw(block(3), 3), Err(Error::Sealed)));
+    assert!(matches!(list.recycle(64), Err(Error::Sealed)));
+    assert_eq!(Error::Sealed.to_string(), "freelist is sealed");
+}
+
+#[test]
+fn fitting_bucket_rounds_up_to_a_bucket() {
+    let list = FreeList::<u8, 5>::new();
+
+    assert_eq!(FreeList::<u8, 5>::MAX_SIZE, 16);
+    assert_eq!(list.fitting_bucket(1), Some(1));
+    assert_eq!(list.fitting_bucket(3), Some(4));
+    assert_eq!(list.fitting_bucket(9), Some(16));
+    assert_eq!(list.fitting_bucket(16), Some(16));
+    assert_eq!(list.fitting_bucket(17), None);
+    assert_eq!(list.fitting_bucket(usize::MAX), None);
+}
+
+#[test]
+fn clear_sorted_frees_each_bucket_in_address_order() {
+    let list = FreeList::<u8, 8>::new();
+    let small = adjacent_blocks(4, 8);
+    let big = adjacent_blocks(3, 32);
+    let freed = RefCell::new(Vec::new());
+
+    for &i in &[2, 0, 3, 1] {
+        list.throw(small[i], 8).unwrap();
+    }
+    for &i in &[1, 2, 0] {
+        list.throw(big[i], 32).unwrap();
+    }
+
+    list.clear_sorted(|ptr, power| freed.borrow_mut().push((power, ptr)));
+
+    let freed = freed.into_inner();
+    let of = |power| {
+        freed
+            .iter()
+            .filter(|&&(p, _)| p == power)
+            .map(|&(_, ptr)| ptr)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(of(3), small);
+    assert_eq!(of(5), big);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn dump_clear_sorted_of_an_empty_dump_does_nothing() {
+    let dump = Dump::<u8>::new();
+
+    dump.clear_sorted(|_| unreachable!());
+
+    let ptr = block(1);
+    dump.throw(ptr).unwrap();
+    dump.clear_sorted(|cleared| assert_eq!(cleared, ptr));
+    assert!(dump.is_empty());
+}
+
+#[test]
+fn free_slots_go_down_with_throws_and_up_with_recycles() {
+    let list = FreeList::<u8, 4>::new();
+    let capacity = Dump::<u8>::capacity();
+
+    assert_eq!(list.free_slots(), 4 * capacity);
+
+    blocks(3, 2).into_iter().for_each(|ptr| list.throw(ptr, 2).unwrap());
+    assert_eq!(list.free_slots(), 4 * capacity - 3);
+    assert_eq!(list.free_slots_for(2).unwrap(), capacity - 3);
+    assert_eq!(list.free_slots_for(4).unwrap(), capacity);
+
+    list.recycle(2).unwrap();
+    assert_eq!(list.free_slots_for(2).unwrap(), capacity - 2);
+}
+
+#[test]
+fn free_slots_of_a_full_bucket_is_0() {
+    let list = FreeList::<u8, 4>::new();
+
+    blocks(Dump::<u8>::capacity(), 8)
+        .into_iter()
+        .for_each(|ptr| list.throw(ptr, 8).unwrap());
+
+    assert_eq!(list.free_slots_for(8).unwrap(), 0);
+    assert!(matches!(list.throw(block(8), 8), Err(Error::BucketFull)));
+    assert!(matches!(list.free_slots_for(5), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.free_slots_for(16), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn bucket_bitmaps_are_those_of_the_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    blocks(2, 4).into_iter().for_each(|ptr| list.throw(ptr, 4).unwrap());
+
+    assert_eq!(list.bucket_bitmaps(4).unwrap(), (0b11, 0b11));
+    assert_eq!(list.bucket_bitmaps(8).unwrap(), (0, 0));
+    assert!(matches!(list.bucket_bitmaps(16), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn snapshot_all_sees_every_bucket_without_taking_anything() {
+    let list = FreeList::<u8, 4>::new();
+    let small = blocks(2, 1);
+    let big = blocks(3, 8);
+    let seen = RefCell::new(Vec::new());
+
+    small.iter().for_each(|&ptr| list.throw(ptr, 1).unwrap());
+    big.iter().for_each(|&ptr| list.throw(ptr, 8).unwrap());
+
+    unsafe { list.snapshot_all(|size, ptrs| seen.borrow_mut().push((size, sorted(ptrs.to_vec())))) };
+
+    assert_eq!(seen.into_inner(), vec![(1, small), (2, vec![]), (4, vec![]), (8, big)]);
+    assert_eq!(list.len(), 5);
+}
+
+#[test]
+fn len_counts_the_parked_pointers_of_every_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert_eq!(list.len(), 0);
+
+    blocks(2, 1).into_iter().for_each(|ptr| list.throw(ptr, 1).unwrap());
+    blocks(3, 4).into_iter().for_each(|ptr| list.throw(ptr, 4).unwrap());
+
+    assert_eq!(list.len(), 5);
+    assert_eq!(list.len_with(Ordering::Acquire), 5);
+    assert_eq!(list.len_with(Ordering::SeqCst), 5);
+
+    list.recycle(4).unwrap();
+    assert_eq!(list.len_with(Ordering::Relaxed), 4);
+    assert_eq!(list.bucket_len(4).unwrap(), 2);
+}
+
+#[test]
+fn dump_len_with_any_ordering() {
+    let dump = Dump::<u8>::new();
+
+    blocks(4, 1).into_iter().for_each(|ptr| dump.throw(ptr).unwrap());
+
+    for ordering in [Ordering::Relaxed, Ordering::Acquire, Ordering::SeqCst] {
+        assert_eq!(dump.len_with(ordering), 4);
+    }
+    assert_eq!(dump.len(), 4);
+}
+
+#[cfg(feature = "parallel-clear")]
+#[test]
+fn clear_parallel_frees_every_bucket() {
+    let list = FreeList::<u8, 8>::new();
+    let freed = std::sync::Mutex::new(Vec::new());
+    let mut expected = Vec::new();
+
+    for power in 0..8 {
+        for ptr in blocks(power + 1, 1 << power) {
+            list.throw(ptr, 1 << power).unwrap();
+            expected.push((power, ptr as usize));
+        }
+    }
+
+    list.clear_parallel(|ptr, power| freed.lock().unwrap().push((power, ptr as usize)));
+
+    let mut freed = freed.into_inner().unwrap();
+    freed.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(freed, expected);
+    assert!(list.is_empty());
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn throws_stop_short_of_the_reserved_slots() {
+    let list = FreeList::<u8, 4>::new();
+    let capacity = list.free_slots_for(4).unwrap();
+    list.set_reserved_slots(4, 2).unwrap();
+
+    let ptrs = blocks(capacity, 4);
+
+    for &ptr in &ptrs[..capacity - 2] {
+        list.throw(ptr, 4).unwrap();
+    }
+
+    assert!(matches!(list.throw(ptrs[capacity - 2], 4), Err(Error::BucketFull)));
+    assert_eq!(list.bucket_len(4).unwrap(), capacity - 2);
+
+    list.throw_priority(ptrs[capacity - 2], 4).unwrap();
+    list.throw_priority(ptrs[capacity - 1], 4).unwrap();
+    assert!(list.is_bucket_full(4).unwrap());
+    assert!(matches!(list.throw_priority(block(4), 4), Err(Error::BucketFull)));
+
+    // Other buckets keep their whole capacity
+    for ptr in blocks(capacity, 2) {
+        list.throw(ptr, 2).unwrap();
+    }
+}
+
+#[test]
+fn recycles_make_room_for_normal_throws_again() {
+    let list = FreeList::<u8, 4>::new();
+    let capacity = list.free_slots_for(8).unwrap();
+    list.set_reserved_slots(8, 1).unwrap();
+
+    for ptr in blocks(capacity, 8) {
+        list.throw_priority(ptr, 8).unwrap();
+    }
+
+    list.recycle(8).unwrap();
+    assert!(matches!(list.throw(block(8), 8), Err(Error::BucketFull)));
+
+    list.recycle(8).unwrap();
+    list.throw(block(8), 8).unwrap();
+}
+
+#[test]
+fn reserving_every_slot_leaves_them_all_to_priority_throws() {
+    let dump = Dump::<u8>::new();
+    dump.set_reserved_slots(Dump::<u8>::capacity() + 1);
+
+    let ptr = block(1);
+    assert_eq!(dump.throw(ptr), Err(ptr));
+    dump.throw_priority(ptr).unwrap();
+
+    dump.set_reserved_slots(0);
+    dump.throw(block(1)).unwrap();
+    assert_eq!(dump.len(), 2);
+}
+
+#[test]
+fn reserved_slots_need_a_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.set_reserved_slots(16, 1), Err(Error::BucketNotAvailable)));
+    assert!(matches!(list.throw_priority(block(16), 16), Err(Error::BucketNotAvailable)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn prefill_all_leaves_the_reserved_slots() {
+    let list = FreeList::<u8, 2>::new();
+    let capacity = list.free_slots_for(1).unwrap();
+    list.set_reserved_slots(1, 3).unwrap();
+
+    let filled = list.prefill_all(capacity, block, |_, _| unreachable!());
+
+    assert_eq!(filled, vec![capacity - 3, capacity]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    tag: u32,
+    len: u32,
+}
+
+#[test]
+fn cast_recycles_typed_pointers_from_the_same_buckets() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(8);
+
+    unsafe { ptr.cast::<Header>().write_unaligned(Header { tag: 7, len: 42 }) };
+    list.throw(ptr, 8).unwrap();
+
+    let typed = unsafe { list.cast::<Header>() };
+    assert_eq!(typed.bucket_len(8).unwrap(), 1);
+
+    let header = typed.recycle(8).unwrap();
+    assert_eq!(header as usize, ptr as usize);
+    assert_eq!(unsafe { header.read_unaligned() }, Header { tag: 7, len: 42 });
+    assert_eq!(list.bucket_len(8).unwrap(), 0);
+}
+
+#[test]
+fn typed_throws_are_recycled_untyped() {
+    let list = FreeList::<u8, 8>::new();
+    let typed = unsafe { list.cast::<Header>() };
+    let header = block(8).cast::<Header>();
+
+    typed.throw(header, 8).unwrap();
+
+    assert_eq!(list.total_bytes_held(), 8);
+    assert_eq!(list.recycle(8).unwrap() as usize, header as usize);
+    assert!(matches!(typed.recycle(8), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn bucket_count_is_n() {
+    assert_eq!(FreeList::<u8, 11>::new().bucket_count(), 11);
+    assert_eq!(FreeList::<u8, 1>::new().bucket_count(), 1);
+}
+
+#[test]
+fn bucket_count_gives_every_size_without_naming_n() {
+    let list = FreeList::<u8, 6>::new();
+    let bucket_count = (&|| list.bucket_count()) as &dyn Fn() -> usize;
+
+    for size in (0..bucket_count()).map(|power| 1 << power) {
+        list.throw(block(size), size).unwrap();
+    }
+
+    let largest = 1 << (bucket_count() - 1);
+    assert_eq!(largest, 32);
+    assert!(matches!(
+        list.throw(block(largest * 2), largest * 2),
+        Err(Error::BucketNotAvailable)
+    ));
+    assert_eq!(list.len(), bucket_count());
+}
+
+#[test]
+fn bucket_len_only_counts_its_own_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    blocks(3, 2).into_iter().for_each(|ptr| list.throw(ptr, 2).unwrap());
+    list.throw(block(8), 8).unwrap();
+
+    assert_eq!(list.bucket_len(1).unwrap(), 0);
+    assert_eq!(list.bucket_len(2).unwrap(), 3);
+    assert_eq!(list.bucket_len(4).unwrap(), 0);
+    assert_eq!(list.bucket_len(8).unwrap(), 1);
+
+    while list.recycle(2).is_ok() {}
+    assert_eq!(list.bucket_len(2).unwrap(), 0);
+    assert_eq!(list.len(), 1);
+}
+
+#[test]
+fn bucket_len_of_a_full_bucket_is_its_capacity() {
+    let list = FreeList::<u8, 4>::new();
+    let capacity = list.free_slots_for(4).unwrap();
+
+    blocks(capacity, 4).into_iter().for_each(|ptr| list.throw(ptr, 4).unwrap());
+
+    assert_eq!(list.bucket_len(4).unwrap(), capacity);
+    assert_eq!(list.len(), capacity);
+}
+
+#[test]
+fn bucket_len_needs_a_bucket() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.bucket_len(3), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.bucket_len(16), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn freelist_is_empty_only_if_every_bucket_is() {
+    let list = FreeList::<u8, 4>::new();
+    assert!(list.is_empty());
+
+    list.throw(block(8), 8).unwrap();
+    assert!(!list.is_empty());
+    assert!(!list.is_bucket_full(8).unwrap());
+
+    list.recycle(8).unwrap();
+    assert!(list.is_empty());
+}
+
+#[test]
+fn is_bucket_full_lets_a_producer_skip_the_throw() {
+    let list = FreeList::<u8, 4>::new();
+
+    let mut thrown = 0;
+    while !list.is_bucket_full(2).unwrap() {
+        list.throw(block(2), 2).unwrap();
+        thrown += 1;
+    }
+
+    assert_eq!(thrown, FreeList::<u8, 4>::TOTAL_CAPACITY / 4);
+    assert!(!list.is_bucket_full(4).unwrap());
+    assert!(matches!(list.is_bucket_full(16), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn new_in_place_initializes_a_boxed_freelist() {
+    type Big = FreeList<u8, 11>;
+
+    let mut uninit = Box::new(MaybeUninit::<Big>::uninit());
+
+    // Garbage left by a previous user of the memory must not leak through
+    unsafe {
+        uninit.as_mut_ptr().cast::<u8>().write_bytes(0xff, std::mem::size_of::<Big>());
+        Big::new_in_place(uninit.as_mut_ptr());
+    }
+    let list = unsafe { Box::from_raw(Box::into_raw(uninit).cast::<Big>()) };
+
+    assert!(list.is_empty());
+    assert!(!list.is_sealed());
+    assert_eq!(list.total_bytes_held(), 0);
+    assert_eq!(list.free_slots(), Big::TOTAL_CAPACITY);
+
+    let ptrs = blocks(3, 1024);
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 1024).unwrap());
+
+    assert_eq!(sorted((0..3).map(|_| list.recycle(1024).unwrap()).collect()), ptrs);
+    assert!(matches!(list.recycle(1024), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn new_in_place_into_memory_from_an_allocator() {
+    let layout = Layout::new::<FreeList<u8, 8>>();
+
+    unsafe {
+        let raw = alloc::alloc(layout).cast::<FreeList<u8, 8>>();
+        assert!(!raw.is_null());
+        FreeList::new_in_place(raw);
+
+        let list = &*raw;
+        let ptr = block(64);
+        list.throw(ptr, 64).unwrap();
+        assert_eq!(list.recycle(64).unwrap(), ptr);
+
+        alloc::dealloc(raw.cast(), layout);
+    }
+}
+
+#[test]
+fn reclaim_until_frees_the_biggest_blocks_first() {
+    let list = FreeList::<u8, 8>::new();
+    let throw = |count, size| blocks(count, size).into_iter().for_each(|ptr| list.throw(ptr, size).unwrap());
+
+    throw(2, 64);
+    throw(2, 32);
+    throw(3, 8);
+
+    let freed = RefCell::new(Vec::new());
+    assert_eq!(list.reclaim_until(100, |_, size| freed.borrow_mut().push(size)), 128);
+    assert_eq!(*freed.borrow(), vec![64, 64]);
+    assert_eq!(list.bucket_len(32).unwrap(), 2);
+    assert_eq!(list.bucket_len(8).unwrap(), 3);
+
+    freed.borrow_mut().clear();
+    assert_eq!(list.reclaim_until(40, |_, size| freed.borrow_mut().push(size)), 64);
+    assert_eq!(*freed.borrow(), vec![32, 32]);
+}
+
+#[test]
+fn reclaim_until_stops_when_the_freelist_runs_dry() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(3, 16);
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 16).unwrap());
+
+    assert_eq!(list.reclaim_until(0, |_, _| unreachable!()), 0);
+
+    let freed = RefCell::new(Vec::new());
+    assert_eq!(list.reclaim_until(usize::MAX, |ptr, _| freed.borrow_mut().push(ptr)), 48);
+    assert_eq!(sorted(freed.into_inner()), ptrs);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn drain_bucket_yields_every_pointer_of_the_bucket() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(5, 16);
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 16).unwrap());
+    list.throw(block(32), 32).unwrap();
+
+    assert_eq!(sorted(list.drain_bucket(16).unwrap().collect()), ptrs);
+    assert_eq!(list.bucket_len(16).unwrap(), 0);
+    assert_eq!(list.bucket_len(32).unwrap(), 1);
+    assert_eq!(list.total_bytes_held(), 32);
+}
+
+#[test]
+fn drain_bucket_can_stop_early() {
+    let list = FreeList::<u8, 8>::new();
+    blocks(5, 16).into_iter().for_each(|ptr| list.throw(ptr, 16).unwrap());
+
+    let taken: Vec<_> = list.drain_bucket(16).unwrap().take(2).collect();
+
+    assert_eq!(taken.len(), 2);
+    assert_eq!(list.bucket_len(16).unwrap(), 3);
+    assert_eq!(list.total_bytes_held(), 3 * 16);
+}
+
+#[test]
+fn drain_bucket_of_an_empty_or_missing_bucket() {
+    let list = FreeList::<u8, 8>::new();
+
+    assert_eq!(list.drain_bucket(16).unwrap().next(), None);
+    assert!(matches!(list.drain_bucket(24), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.drain_bucket(256), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn null_pointers_are_rejected() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.throw(ptr::null_mut(), 4), Err(Error::NullPointer)));
+    assert!(matches!(list.throw_priority(ptr::null_mut(), 4), Err(Error::NullPointer)));
+    assert!(list.is_empty());
+    assert_eq!(list.total_bytes_held(), 0);
+    assert!(matches!(list.recycle(4), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn null_is_checked_before_the_size() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.throw(ptr::null_mut(), 3), Err(Error::NullPointer)));
+    assert!(matches!(list.throw(ptr::null_mut(), 64), Err(Error::NullPointer)));
+    assert_eq!(Error::NullPointer.to_string(), "pointer is null");
+}
+
+#[test]
+fn dump_rejects_null_pointers() {
+    let dump = Dump::<u8>::new();
+
+    assert_eq!(dump.throw(ptr::null_mut()), Err(ptr::null_mut()));
+    assert_eq!(dump.throw_priority(ptr::null_mut()), Err(ptr::null_mut()));
+    assert!(dump.is_empty());
+    assert_eq!(dump.free_slots(), Dump::<u8>::capacity());
+}
+
+#[test]
+fn freelists_can_be_fields_of_structs_deriving_default() {
+    #[derive(Default)]
+    struct Cache {
+        list: FreeList<u8, 8>,
+        dump: Dump<u8>,
+        hits: usize,
+    }
+
+    let cache = Cache::default();
+    assert!(cache.list.is_empty());
+    assert!(cache.dump.is_empty());
+    assert_eq!(cache.hits, 0);
+
+    let ptr = block(16);
+    cache.list.throw(ptr, 16).unwrap();
+    assert_eq!(cache.list.recycle(16).unwrap(), ptr);
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout};
+
+use freelist::{Dump, FreeListAlloc};
+
+#[test]
+fn freed_blocks_are_parked_and_reused() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layout = Layout::from_size_align(24, 8).unwrap();
+
+    unsafe {
+        let ptr = alloc.alloc(layout);
+        assert!(!ptr.is_null());
+        ptr.write_bytes(1, 24);
+
+        alloc.dealloc(ptr, layout);
+        assert_eq!(alloc.bucket_len(32).unwrap(), 1);
+
+        assert_eq!(alloc.alloc(layout), ptr);
+        assert!(alloc.is_empty());
+
+        alloc.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn layouts_of_the_same_bucket_share_blocks() {
+    let alloc = FreeListAlloc::<8>::new();
+    let small = Layout::from_size_align(20, 4).unwrap();
+    let aligned = Layout::from_size_align(1, 32).unwrap();
+
+    unsafe {
+        let ptr = alloc.alloc(small);
+        alloc.dealloc(ptr, small);
+
+        // 32 bytes aligned to 32 is what any layout of the bucket needs
+        assert_eq!(alloc.alloc(aligned), ptr);
+        assert_eq!(ptr as usize % 32, 0);
+
+        alloc.dealloc(ptr, aligned);
+    }
+}
+
+#[test]
+fn blocks_are_aligned_to_their_bucket() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layout = Layout::from_size_align(8, 64).unwrap();
+
+    unsafe {
+        let ptrs: Vec<_> = (0..4).map(|_| alloc.alloc(layout)).collect();
+        assert!(ptrs.iter().all(|&ptr| ptr as usize % 64 == 0));
+
+        ptrs.iter().for_each(|&ptr| alloc.dealloc(ptr, layout));
+        assert_eq!(alloc.bucket_len(64).unwrap(), 4);
+    }
+}
+
+#[test]
+fn layouts_too_big_for_a_bucket_arent_parked() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layout = Layout::from_size_align(1000, 8).unwrap();
+
+    unsafe {
+        let ptr = alloc.alloc(layout);
+        assert!(!ptr.is_null());
+        ptr.write_bytes(1, 1000);
+
+        alloc.dealloc(ptr, layout);
+    }
+
+    assert!(alloc.is_empty());
+}
+
+#[test]
+fn blocks_a_full_bucket_cant_take_go_back_to_the_system() {
+    let alloc = FreeListAlloc::<4>::default();
+    let layout = Layout::new::<u64>();
+    let capacity = Dump::<u8>::capacity();
+
+    unsafe {
+        let ptrs: Vec<_> = (0..capacity + 3).map(|_| alloc.alloc(layout)).collect();
+        ptrs.iter().for_each(|&ptr| alloc.dealloc(ptr, layout));
+    }
+
+    assert_eq!(alloc.bucket_len(8).unwrap(), capacity);
+    assert_eq!(alloc.len(), capacity);
+}
+
+#[test]
+fn default_starts_with_an_empty_freelist() {
+    let alloc = FreeListAlloc::<8>::default();
+    assert!(alloc.is_empty());
+}
+
+#[test]
+fn clear_gives_parked_blocks_back() {
+    let alloc = FreeListAlloc::<8>::new();
+    let layouts = [
+        Layout::new::<u8>(),
+        Layout::new::<[u64; 3]>(),
+        Layout::from_size_align(8, 64).unwrap(),
+    ];
+
+    unsafe {
+        let ptrs: Vec<_> = layouts.iter().map(|&layout| alloc.alloc(layout)).collect();
+        ptrs.iter().zip(layouts).for_each(|(&ptr, layout)| alloc.dealloc(ptr, layout));
+    }
+
+    assert_eq!(alloc.len(), 3);
+    assert_eq!(alloc.total_bytes_held(), 1 + 32 + 64);
+
+    alloc.clear();
+    assert!(alloc.is_empty());
+    assert_eq!(alloc.total_bytes_held(), 0);
+    assert!(alloc.bucket_stats().iter().all(|bucket| bucket.parked == 0));
+}
@@ -0,0 +1,38 @@
+//! [FreeListAlloc] as the `#[global_allocator]` of this test binary.
+
+#![cfg(feature = "std")]
+
+use std::thread;
+
+use freelist::FreeListAlloc;
+
+#[global_allocator]
+static ALLOC: FreeListAlloc<12> = FreeListAlloc::new();
+
+#[test]
+fn collections_work_on_top_of_the_freelist() {
+    let handles: Vec<_> = (0..4)
+        .map(|seed| {
+            thread::spawn(move || {
+                let mut total = 0;
+
+                for round in 0..200 {
+                    let strings: Vec<String> = (0..16).map(|i| "x".repeat((seed + round + i) % 300)).collect();
+                    let numbers: Vec<u64> = (0..round as u64).collect();
+
+                    total += strings.iter().map(String::len).sum::<usize>();
+                    assert_eq!(numbers.iter().sum::<u64>(), (0..round as u64).sum());
+                }
+
+                total
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        assert!(handle.join().unwrap() > 0);
+    }
+
+    // Freed blocks of the churn above were parked for reuse
+    assert!(!ALLOC.is_empty());
+}
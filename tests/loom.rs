@@ -0,0 +1,171 @@
+//! Model checks the bitmap protocol of [Dump] and [SpmcDump] with loom.
+//!
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`.
+
+#![cfg(loom)]
+
+use freelist::{Dump, SpmcDump};
+use loom::sync::Arc;
+use loom::thread;
+
+/// Recycles whatever is left in `dump` into `seen`, then checks
+/// that `seen` has every one of `thrown` exactly once.
+fn assert_all_once(dump: &Dump<u8>, mut seen: Vec<usize>, thrown: &[usize]) {
+    while let Some(raw) = dump.recycle() {
+        seen.push(raw as usize);
+    }
+
+    seen.sort_unstable();
+    assert_eq!(seen, thrown);
+}
+
+#[test]
+fn throw_and_recycle_on_both_threads() {
+    loom::model(|| {
+        let dump = Arc::new(Dump::<u8>::new());
+        let other = dump.clone();
+
+        let handle = thread::spawn(move || {
+            other.throw(0x10 as *mut u8).unwrap();
+            other.recycle().map(|raw| raw as usize)
+        });
+
+        dump.throw(0x20 as *mut u8).unwrap();
+        let mut seen: Vec<usize> = dump.recycle().map(|raw| raw as usize).into_iter().collect();
+        seen.extend(handle.join().unwrap());
+
+        assert_all_once(&dump, seen, &[0x10, 0x20]);
+    });
+}
+
+#[test]
+fn throw_while_recycling() {
+    loom::model(|| {
+        let dump = Arc::new(Dump::<u8>::new());
+        let other = dump.clone();
+
+        let handle = thread::spawn(move || {
+            other.throw(0x10 as *mut u8).unwrap();
+            other.throw(0x20 as *mut u8).unwrap();
+        });
+
+        let seen: Vec<usize> = dump.recycle().map(|raw| raw as usize).into_iter().collect();
+        handle.join().unwrap();
+
+        assert_all_once(&dump, seen, &[0x10, 0x20]);
+    });
+}
+
+#[test]
+fn throw_while_clearing() {
+    loom::model(|| {
+        let dump = Arc::new(Dump::<u8>::new());
+        let other = dump.clone();
+
+        dump.throw(0x10 as *mut u8).unwrap();
+
+        let handle = thread::spawn(move || {
+            other.throw(0x20 as *mut u8).unwrap();
+        });
+
+        let cleared = std::cell::RefCell::new(Vec::new());
+        dump.clear(|raw| cleared.borrow_mut().push(raw as usize));
+        handle.join().unwrap();
+
+        assert_all_once(&dump, cleared.into_inner(), &[0x10, 0x20]);
+    });
+}
+
+#[test]
+fn throw_while_taking() {
+    loom::model(|| {
+        let dump = Arc::new(Dump::<u8>::new());
+        let other = dump.clone();
+
+        dump.throw(0x10 as *mut u8).unwrap();
+
+        let handle = thread::spawn(move || {
+            other.throw(0x20 as *mut u8).unwrap();
+        });
+
+        let taken = dump.take().into_iter().map(|raw| raw as usize).collect();
+        handle.join().unwrap();
+
+        assert_all_once(&dump, taken, &[0x10, 0x20]);
+    });
+}
+
+#[test]
+fn recycle_while_clearing() {
+    loom::model(|| {
+        let dump = Arc::new(Dump::<u8>::new());
+        let other = dump.clone();
+
+        dump.throw(0x10 as *mut u8).unwrap();
+        dump.throw(0x20 as *mut u8).unwrap();
+
+        let handle = thread::spawn(move || other.recycle().map(|raw| raw as usize));
+
+        let cleared = std::cell::RefCell::new(Vec::new());
+        dump.clear(|raw| cleared.borrow_mut().push(raw as usize));
+
+        // Neither handed out twice nor lost
+        let mut seen = cleared.into_inner();
+        seen.extend(handle.join().unwrap());
+
+        assert_all_once(&dump, seen, &[0x10, 0x20]);
+    });
+}
+
+#[test]
+fn spmc_throws_on_both_threads_while_recycling() {
+    loom::model(|| {
+        let dump = Arc::new(SpmcDump::<u8>::new());
+        let other = dump.clone();
+
+        let handle = thread::spawn(move || {
+            other.throw(0x10 as *mut u8).unwrap();
+        });
+
+        dump.throw(0x20 as *mut u8).unwrap();
+
+        // Only this thread recycles
+        let mut seen: Vec<usize> = unsafe { dump.recycle() }.map(|raw| raw as usize).into_iter().collect();
+        handle.join().unwrap();
+
+        while let Some(raw) = unsafe { dump.recycle() } {
+            seen.push(raw as usize);
+        }
+
+        seen.sort_unstable();
+        assert_eq!(seen, [0x10, 0x20]);
+    });
+}
+
+#[test]
+fn spmc_spot_reused_while_recycling() {
+    loom::model(|| {
+        let dump = Arc::new(SpmcDump::<u8, 2>::new());
+        let other = dump.clone();
+
+        dump.throw(0x10 as *mut u8).unwrap();
+        dump.throw(0x20 as *mut u8).unwrap();
+
+        // The producer can only get a spot once the consumer released one
+        let handle = thread::spawn(move || {
+            while other.throw(0x30 as *mut u8).is_err() {
+                thread::yield_now();
+            }
+        });
+
+        let mut seen: Vec<usize> = unsafe { dump.recycle() }.map(|raw| raw as usize).into_iter().collect();
+        handle.join().unwrap();
+
+        while let Some(raw) = unsafe { dump.recycle() } {
+            seen.push(raw as usize);
+        }
+
+        seen.sort_unstable();
+        assert_eq!(seen, [0x10, 0x20, 0x30]);
+    });
+}
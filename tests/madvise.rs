@@ -0,0 +1,40 @@
+#![cfg(all(feature = "madvise", target_os = "linux"))]
+
+mod common;
+
+use std::cell::RefCell;
+
+use common::{block, sorted};
+use freelist::{Error, FreeList};
+
+#[test]
+fn clear_bucket_madvise_hands_every_block_to_f() {
+    let list = FreeList::<u8, 16>::new();
+    let big = vec![block(1 << 14), block(1 << 14)];
+    let small = block(16);
+    let cleared = RefCell::new(Vec::new());
+
+    big.iter().for_each(|&ptr| list.throw(ptr, 1 << 14).unwrap());
+    list.throw(small, 16).unwrap();
+
+    list.clear_bucket_madvise(1 << 14, |ptr| cleared.borrow_mut().push(ptr))
+        .unwrap();
+    assert_eq!(sorted(cleared.take()), sorted(big));
+    assert_eq!(list.total_bytes_held(), 16);
+
+    // Smaller than a page, so nothing to advise
+    list.clear_bucket_madvise(16, |ptr| cleared.borrow_mut().push(ptr)).unwrap();
+    assert_eq!(cleared.into_inner(), vec![small]);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn clear_bucket_madvise_checks_the_size() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.clear_bucket_madvise(3, |_| {}), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(
+        list.clear_bucket_madvise(64, |_| {}),
+        Err(Error::BucketNotAvailable)
+    ));
+}
@@ -0,0 +1,33 @@
+#![cfg(all(feature = "mlock", unix))]
+
+mod common;
+
+use common::blocks;
+use freelist::FreeList;
+
+#[test]
+fn nothing_to_lock_in_an_empty_freelist() {
+    let list = FreeList::<u8, 4>::new();
+
+    unsafe {
+        list.mlock_blocks().unwrap();
+        list.munlock_blocks().unwrap();
+    }
+}
+
+#[test]
+fn parked_blocks_are_locked_and_stay_parked() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(3, 64);
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 64).unwrap());
+
+    // Locking is up to the limits of the process, which may not allow any
+    match unsafe { list.mlock_blocks() } {
+        Ok(()) => unsafe { list.munlock_blocks().unwrap() },
+        Err(err) => assert!(matches!(err.raw_os_error(), Some(libc::ENOMEM | libc::EPERM))),
+    }
+
+    assert_eq!(list.bucket_len(64).unwrap(), 3);
+    assert_eq!(list.total_bytes_held(), 3 * 64);
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "alloc")]
+
+use std::{cell::Cell, rc::Rc};
+
+use freelist::{Dump, Pool};
+
+/// Counts how many times it was dropped in `drops`.
+struct Tracked {
+    value: u64,
+    drops: Rc<Cell<usize>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+#[test]
+fn dropped_guards_park_their_block_for_the_next_get() {
+    let pool = Pool::<[u64; 3], 8>::new();
+
+    let first = pool.get(|| [1, 2, 3]);
+    assert_eq!(*first, [1, 2, 3]);
+    let addr = &*first as *const [u64; 3];
+    drop(first);
+
+    assert_eq!(pool.len(), 1);
+
+    let second = pool.get(|| [4, 5, 6]);
+    assert_eq!(&*second as *const [u64; 3], addr);
+    assert_eq!(*second, [4, 5, 6]);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn values_are_dropped_once_with_their_guard() {
+    let drops = Rc::new(Cell::new(0));
+    let pool = Pool::<Tracked, 8>::default();
+
+    let mut guard = pool.get(|| Tracked {
+        value: 1,
+        drops: Rc::clone(&drops),
+    });
+    guard.value += 1;
+    assert_eq!(guard.value, 2);
+    assert_eq!(drops.get(), 0);
+
+    drop(guard);
+    assert_eq!(drops.get(), 1);
+
+    // Parked blocks hold no value, so dropping the pool drops nothing more
+    drop(pool);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn blocks_a_full_bucket_cant_take_are_freed() {
+    let pool = Pool::<u64, 8>::new();
+    let capacity = Dump::<u64>::capacity();
+
+    let guards: Vec<_> = (0..capacity as u64 + 5).map(|i| pool.get(|| i)).collect();
+    assert!(guards.iter().enumerate().all(|(i, guard)| **guard == i as u64));
+    drop(guards);
+
+    assert_eq!(pool.len(), capacity);
+}
+
+#[test]
+fn values_too_big_for_a_bucket_are_never_parked() {
+    let pool = Pool::<[u8; 200], 4>::new();
+
+    let guard = pool.get(|| [7; 200]);
+    assert!(guard.iter().all(|&byte| byte == 7));
+    drop(guard);
+
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn take_clone_writes_a_clone_of_the_template() {
+    let pool = Pool::<Vec<u32>, 8>::new();
+    let template = vec![1, 2, 3];
+
+    let first = pool.get(Vec::new);
+    let addr = &*first as *const Vec<u32>;
+    drop(first);
+
+    let mut copy = pool.take_clone(&template);
+    assert_eq!(&*copy as *const Vec<u32>, addr);
+    copy.push(4);
+
+    assert_eq!(*copy, [1, 2, 3, 4]);
+    assert_eq!(template, [1, 2, 3]);
+}
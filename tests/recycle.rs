@@ -0,0 +1,586 @@
+mod common;
+
+use std::{alloc::Layout, cell::RefCell, sync::Arc, thread};
+
+#[cfg(feature = "alloc")]
+use common::sorted;
+use common::{block, blocks, region};
+use freelist::{Dump, Error, FreeList, RecycleError};
+
+#[test]
+fn recycle_if_above_keeps_a_reserve() {
+    let list = FreeList::<u8, 8>::new();
+
+    blocks(3, 16).into_iter().for_each(|ptr| list.throw(ptr, 16).unwrap());
+
+    assert!(list.recycle_if_above(16, 2).unwrap().is_some());
+    assert_eq!(list.recycle_if_above(16, 2).unwrap(), None);
+    assert_eq!(list.bucket_len(16).unwrap(), 2);
+    assert_eq!(list.total_bytes_held(), 32);
+
+    // Plain recycles can still dip into the reserve
+    list.recycle(16).unwrap();
+    assert!(list.recycle_if_above(16, 0).unwrap().is_some());
+    assert_eq!(list.recycle_if_above(16, 0).unwrap(), None);
+}
+
+#[test]
+fn recycle_if_above_checks_the_size() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.recycle_if_above(3, 0), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.recycle_if_above(16, 0), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn dump_recycle_if_above() {
+    let dump = Dump::<u8>::new();
+    let ptr = block(1);
+
+    assert_eq!(dump.recycle_if_above(0), None);
+
+    dump.throw(ptr).unwrap();
+    assert_eq!(dump.recycle_if_above(1), None);
+    assert_eq!(dump.recycle_if_above(0), Some(ptr));
+}
+
+#[test]
+fn try_recycle_once_without_contention() {
+    let dump = Dump::<u8>::new();
+    let ptr = block(1);
+
+    assert_eq!(dump.try_recycle_once(), Err(RecycleError::Empty));
+
+    dump.throw(ptr).unwrap();
+    assert_eq!(dump.try_recycle_once(), Ok(ptr));
+    assert_eq!(dump.try_recycle_once(), Err(RecycleError::Empty));
+}
+
+#[test]
+fn freelist_try_recycle_once() {
+    let list = FreeList::<u8, 4>::new();
+    let ptr = block(8);
+
+    assert!(matches!(list.try_recycle_once(8), Err(Error::BucketEmpty)));
+
+    list.throw(ptr, 8).unwrap();
+    assert_eq!(list.try_recycle_once(8).unwrap(), ptr);
+    assert_eq!(list.total_bytes_held(), 0);
+    assert!(matches!(list.try_recycle_once(6), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.try_recycle_once(16), Err(Error::BucketNotAvailable)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn recycle_refill_appends_up_to_want() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(5, 32);
+    let mut local = vec![block(32)];
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 32).unwrap());
+
+    assert_eq!(list.recycle_refill(32, &mut local, 3, |_, _| unreachable!()).unwrap(), 3);
+    assert_eq!(local.len(), 4);
+    assert_eq!(list.bucket_len(32).unwrap(), 2);
+
+    // Asking for more than there is gets the rest
+    assert_eq!(list.recycle_refill(32, &mut local, 10, |_, _| unreachable!()).unwrap(), 2);
+    assert_eq!(sorted(local[1..].to_vec()), ptrs);
+
+    assert_eq!(list.recycle_refill(32, &mut local, 10, |_, _| unreachable!()).unwrap(), 0);
+    assert!(matches!(
+        list.recycle_refill(24, &mut local, 1, |_, _| unreachable!()),
+        Err(Error::SizeNotPowerOf2)
+    ));
+}
+
+#[cfg(all(feature = "alloc", feature = "canary"))]
+#[test]
+fn recycle_refill_rejects_corrupted_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(3, 8);
+    let mut local = Vec::new();
+    let mut rejected = Vec::new();
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 8).unwrap());
+    unsafe { ptrs[1].add(8).write(0) };
+
+    let got = list
+        .recycle_refill(8, &mut local, 3, |ptr, err| rejected.push((ptr, err)))
+        .unwrap();
+
+    assert_eq!(got, 2);
+    assert_eq!(sorted(local), vec![ptrs[0], ptrs[2]]);
+    assert!(matches!(rejected[..], [(ptr, Error::Corrupted(addr))] if ptr == ptrs[1] && addr == ptr as usize));
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn dump_recycle_into_takes_the_lowest_spots() {
+    let dump = Dump::<u8>::new();
+    let mut local = Vec::new();
+
+    blocks(4, 1).into_iter().for_each(|ptr| dump.throw(ptr).unwrap());
+
+    assert_eq!(dump.recycle_into(&mut local, 0), 0);
+    assert_eq!(dump.recycle_into(&mut local, 3), 3);
+    assert_eq!(dump.reader_bitmap_snapshot(), 0b1000);
+    assert_eq!(dump.writer_bitmap_snapshot(), 0b1000);
+}
+
+#[test]
+fn checked_out_pointers_go_back_to_their_bucket() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(64);
+
+    list.throw(ptr, 64).unwrap();
+
+    let (recycled, token) = list.recycle_checked_out(64).unwrap();
+    assert_eq!(recycled, ptr);
+    assert_eq!(token.size(), 64);
+    assert_eq!(format!("{:?}", token), "ThrowToken { power: 6 }");
+
+    list.throw_checked_in(recycled, token).unwrap();
+    assert_eq!(list.bucket_len(64).unwrap(), 1);
+    assert!(matches!(list.recycle_checked_out(32), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn throw_checked_in_into_a_full_bucket() {
+    let list = FreeList::<u8, 8>::new();
+
+    list.throw(block(4), 4).unwrap();
+    let (ptr, token) = list.recycle_checked_out(4).unwrap();
+
+    blocks(Dump::<u8>::capacity(), 4)
+        .into_iter()
+        .for_each(|ptr| list.throw(ptr, 4).unwrap());
+
+    assert!(matches!(list.throw_checked_in(ptr, token), Err(Error::BucketFull)));
+}
+
+#[test]
+fn recycle_with_slot_reports_the_bit_it_cleared() {
+    let dump = Dump::<u8>::new();
+    let ptrs = blocks(3, 1);
+
+    for &ptr in &ptrs {
+        dump.throw(ptr).unwrap();
+    }
+
+    while !dump.is_empty() {
+        let before = dump.reader_bitmap_snapshot();
+        let (ptr, slot) = dump.recycle_with_slot().unwrap();
+
+        assert!(ptrs.contains(&ptr));
+        assert_eq!(before ^ dump.reader_bitmap_snapshot(), 1 << slot);
+    }
+
+    assert_eq!(dump.recycle_with_slot(), None);
+}
+
+#[test]
+fn freelist_recycle_with_slot_uses_the_bucket_of_size() {
+    let list = FreeList::<u8, 4>::new();
+    let ptr = block(4);
+
+    list.throw(block(2), 2).unwrap();
+    list.throw(ptr, 4).unwrap();
+
+    let (reader, _) = list.bucket_bitmaps(4).unwrap();
+    let (recycled, slot) = list.recycle_with_slot(4).unwrap();
+
+    assert_eq!(recycled, ptr);
+    assert_eq!(reader, 1 << slot);
+    assert_eq!(list.total_bytes_held(), 2);
+    assert!(matches!(list.recycle_with_slot(4), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_with_slot(3), Err(Error::SizeNotPowerOf2)));
+}
+
+#[test]
+fn recycle_with_power_matches_what_clear_passes() {
+    let list = FreeList::<u8, 8>::new();
+    let cleared = RefCell::new(Vec::new());
+
+    for size in [1, 16, 64] {
+        let ptr = block(size);
+        list.throw(ptr, size).unwrap();
+
+        assert_eq!(list.recycle_with_power(size).unwrap(), (ptr, size.trailing_zeros()));
+        list.throw(ptr, size).unwrap();
+
+        list.clear(|ptr, power| cleared.borrow_mut().push((ptr, power as u32)));
+        assert_eq!(*cleared.borrow(), vec![(ptr, size.trailing_zeros())]);
+        cleared.borrow_mut().clear();
+    }
+
+    assert!(matches!(list.recycle_with_power(4), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_with_power(3), Err(Error::SizeNotPowerOf2)));
+}
+
+#[test]
+fn recycle_within_doesnt_look_past_its_budget() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(64);
+    list.throw(ptr, 64).unwrap();
+
+    // 8, 16 and 32 are scanned, 64 is one bucket too far
+    assert!(matches!(list.recycle_within(8, 3), Err(Error::BucketEmpty)));
+    assert_eq!(list.bucket_len(64).unwrap(), 1);
+
+    assert_eq!(list.recycle_within(8, 4).unwrap(), (ptr, 64));
+}
+
+#[test]
+fn recycle_within_prefers_the_bucket_of_size() {
+    let list = FreeList::<u8, 8>::new();
+    let small = block(8);
+    let big = block(16);
+
+    list.throw(big, 16).unwrap();
+    list.throw(small, 8).unwrap();
+
+    assert_eq!(list.recycle_within(8, 2).unwrap(), (small, 8));
+    assert_eq!(list.recycle_within(8, 2).unwrap(), (big, 16));
+    assert!(matches!(list.recycle_within(8, 8), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn recycle_within_a_budget_of_0_scans_nothing() {
+    let list = FreeList::<u8, 8>::new();
+    list.throw(block(8), 8).unwrap();
+
+    assert!(matches!(list.recycle_within(8, 0), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_within(256, 1), Err(Error::BucketNotAvailable)));
+    assert_eq!(list.bucket_len(8).unwrap(), 1);
+}
+
+#[test]
+fn contended_recycles_dont_lose_anything() {
+    const PER_THREAD: usize = 2000;
+
+    let dump = Arc::new(Dump::<usize>::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let dump = Arc::clone(&dump);
+
+            thread::spawn(move || {
+                let mut hits = 0;
+
+                for i in 1..=PER_THREAD {
+                    // Only the address is ever looked at
+                    while dump.throw(i as *mut usize).is_err() {}
+
+                    match dump.try_recycle_once() {
+                        Ok(_) => hits += 1,
+                        Err(RecycleError::Contended | RecycleError::Empty) => {}
+                    }
+                }
+
+                hits
+            })
+        })
+        .collect();
+
+    let hits: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+
+    // Whatever wasn't recycled, contended or not, is still in the dump
+    assert_eq!(hits + dump.len(), 4 * PER_THREAD);
+}
+
+#[test]
+fn try_recycle_once_of_a_freelist_maps_empty_to_bucket_empty() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(matches!(list.try_recycle_once(4), Err(Error::BucketEmpty)));
+    assert_eq!(Error::Contended.to_string(), "bucket is being used by other threads");
+    assert_ne!(RecycleError::Empty, RecycleError::Contended);
+    assert_eq!(format!("{:?}", RecycleError::Contended), "Contended");
+}
+
+#[test]
+fn recycle_uninit_hands_out_a_buffer_as_is() {
+    let list = FreeList::<u8, 8>::new();
+    list.throw(block(32), 32).unwrap();
+
+    let (ptr, len) = list.recycle_uninit(32).unwrap();
+    assert_eq!(len, 32);
+
+    // Used like a buffer `read(2)` fills, then thrown back
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+    buf.copy_from_slice(&[7; 32]);
+    list.throw(ptr, len).unwrap();
+
+    // What the last user wrote is still there, nothing is zeroed
+    let (again, len) = list.recycle_uninit(32).unwrap();
+    assert_eq!(again, ptr);
+    assert_eq!(unsafe { std::slice::from_raw_parts(again, len) }, &[7; 32]);
+}
+
+#[test]
+fn recycle_uninit_fails_like_recycle() {
+    let list = FreeList::<u8, 8>::new();
+
+    assert!(matches!(list.recycle_uninit(32), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_uninit(24), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.recycle_uninit(256), Err(Error::BucketNotAvailable)));
+
+    list.drain_and_seal(|_, _| {});
+    assert!(matches!(list.recycle_uninit(32), Err(Error::Sealed)));
+}
+
+#[test]
+fn get_or_alloc_recycles_from_the_rounded_bucket() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(32);
+    list.throw(ptr, 32).unwrap();
+
+    assert_eq!(list.get_or_alloc(20, |_| unreachable!(), |_| unreachable!()), (ptr, 32));
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn get_or_alloc_allocates_the_rounded_size_otherwise() {
+    let list = FreeList::<u8, 8>::new();
+    let fresh = block(256);
+
+    // Empty bucket
+    assert_eq!(
+        list.get_or_alloc(20, |size| if size == 32 { fresh } else { unreachable!() }, |_| unreachable!()),
+        (fresh, 32)
+    );
+
+    // No bucket that big
+    assert_eq!(
+        list.get_or_alloc(
+            200,
+            |size| if size == 256 { fresh } else { unreachable!() },
+            |_| unreachable!()
+        ),
+        (fresh, 256)
+    );
+
+    // Can't be rounded up, so it's passed as is
+    let huge = usize::MAX - 1;
+    assert_eq!(
+        list.get_or_alloc(
+            huge,
+            |size| if size == huge { fresh } else { unreachable!() },
+            |_| unreachable!()
+        ),
+        (fresh, huge)
+    );
+
+    list.drain_and_seal(|_, _| {});
+    assert_eq!(list.get_or_alloc(8, |_| fresh, |_| unreachable!()), (fresh, 8));
+}
+
+#[test]
+fn put_or_free_retains_what_fits() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(32);
+
+    assert!(list.put_or_free(ptr, 20, |_| unreachable!()));
+    assert_eq!(list.recycle(32).unwrap(), ptr);
+}
+
+#[test]
+fn put_or_free_frees_what_the_freelist_cant_take() {
+    let list = FreeList::<u8, 2>::new();
+    let freed = RefCell::new(Vec::new());
+    let free = |ptr| freed.borrow_mut().push(ptr);
+
+    for ptr in blocks(list.free_slots_for(2).unwrap(), 2) {
+        assert!(list.put_or_free(ptr, 2, free));
+    }
+
+    let (full, too_big, huge) = (block(2), block(4), block(1));
+    assert!(!list.put_or_free(full, 2, free));
+    assert!(!list.put_or_free(too_big, 3, free));
+    assert!(!list.put_or_free(huge, usize::MAX, free));
+
+    assert_eq!(*freed.borrow(), vec![full, too_big, huge]);
+}
+
+#[test]
+fn dropped_checkouts_come_back_on_the_next_recycle() {
+    let list = FreeList::<u8, 8>::new();
+    let fresh = block(32);
+
+    let guard = list.checkout(20, |size| if size == 32 { fresh } else { unreachable!() }, |_| unreachable!());
+    assert_eq!((guard.as_ptr(), guard.size()), (fresh, 32));
+    assert!(list.is_empty());
+
+    drop(guard);
+    assert_eq!(list.bucket_len(32).unwrap(), 1);
+
+    let guard = list.checkout(32, |_| unreachable!(), |_| unreachable!());
+    assert_eq!(guard.as_ptr(), fresh);
+    assert!(list.is_empty());
+    drop(guard);
+
+    assert_eq!(list.recycle(32).unwrap(), fresh);
+}
+
+#[test]
+fn checkouts_the_freelist_cant_take_back_are_freed() {
+    let list = FreeList::<u8, 2>::new();
+    let freed = RefCell::new(Vec::new());
+    let free = |ptr| freed.borrow_mut().push(ptr);
+
+    let (extra, too_big) = (block(2), block(4));
+    let guard = list.checkout(2, |_| extra, free);
+
+    // Fills the bucket while the block is out
+    for ptr in blocks(list.free_slots_for(2).unwrap(), 2) {
+        list.throw(ptr, 2).unwrap();
+    }
+
+    drop(guard);
+    assert_eq!(*freed.borrow(), vec![extra]);
+
+    // No bucket that big
+    drop(list.checkout(3, |_| too_big, free));
+    assert_eq!(*freed.borrow(), vec![extra, too_big]);
+}
+
+#[test]
+fn into_raw_keeps_the_block_out_of_the_freelist() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(16);
+    list.throw(ptr, 16).unwrap();
+
+    let guard = list.checkout(9, |_| unreachable!(), |_| unreachable!());
+    assert_eq!(guard.into_raw(), (ptr, 16));
+
+    assert!(list.is_empty());
+}
+
+#[test]
+fn recycle_at_least_rounds_the_size_up() {
+    let list = FreeList::<u8, 8>::new();
+
+    for (size, rounded) in [(1, 1), (3, 4), (17, 32), (64, 64), (100, 128)] {
+        let ptr = block(rounded);
+        list.throw(ptr, rounded).unwrap();
+
+        assert_eq!(list.recycle_at_least(size).unwrap(), (ptr, rounded));
+    }
+}
+
+#[test]
+fn recycle_at_least_rejects_sizes_without_a_bucket() {
+    let list = FreeList::<u8, 8>::new();
+
+    assert!(matches!(list.recycle_at_least(0), Err(Error::ZeroSize)));
+    assert!(matches!(list.recycle_at_least(17), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_at_least(129), Err(Error::BucketNotAvailable)));
+    assert!(matches!(list.recycle_at_least(usize::MAX), Err(Error::BucketNotAvailable)));
+    assert_eq!(Error::ZeroSize.to_string(), "size should not be 0");
+}
+
+#[test]
+fn recycle_or_larger_falls_back_to_the_next_bucket_with_something() {
+    let list = FreeList::<u8, 8>::new();
+    let (exact, bigger, biggest) = (block(8), block(32), block(128));
+
+    list.throw(biggest, 128).unwrap();
+    list.throw(bigger, 32).unwrap();
+    list.throw(exact, 8).unwrap();
+
+    assert_eq!(list.recycle_or_larger(8).unwrap(), (exact, 8));
+    assert_eq!(list.recycle_or_larger(8).unwrap(), (bigger, 32));
+    assert_eq!(list.recycle_or_larger(8).unwrap(), (biggest, 128));
+    assert!(matches!(list.recycle_or_larger(8), Err(Error::BucketEmpty)));
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn recycle_or_larger_never_goes_smaller() {
+    let list = FreeList::<u8, 8>::new();
+    list.throw(block(4), 4).unwrap();
+
+    assert!(matches!(list.recycle_or_larger(8), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle_or_larger(12), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.recycle_or_larger(256), Err(Error::BucketNotAvailable)));
+    assert_eq!(list.bucket_len(4).unwrap(), 1);
+}
+
+#[test]
+fn recycle_aligned_leaves_misaligned_pointers_in_the_bucket() {
+    let list = FreeList::<u8, 8>::new();
+
+    let base = region(128);
+    let aligned = base.wrapping_add(base.align_offset(32));
+    let misaligned = aligned.wrapping_add(40);
+
+    list.throw(misaligned, 16).unwrap();
+    list.throw(aligned, 16).unwrap();
+
+    assert_eq!(list.recycle_aligned(16, 32).unwrap(), aligned);
+    assert!(matches!(list.recycle_aligned(16, 32), Err(Error::BucketEmpty)));
+    assert_eq!(list.bucket_len(16).unwrap(), 1);
+
+    assert_eq!(list.recycle_aligned(16, 8).unwrap(), misaligned);
+    assert_eq!(list.total_bytes_held(), 0);
+}
+
+#[test]
+fn recycle_aligned_needs_a_power_of_2() {
+    let list = FreeList::<u8, 8>::new();
+    list.throw(block(16), 16).unwrap();
+
+    assert!(matches!(list.recycle_aligned(16, 24), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.recycle_aligned(16, 0), Err(Error::SizeNotPowerOf2)));
+    assert!(matches!(list.recycle_aligned(512, 8), Err(Error::BucketNotAvailable)));
+    assert!(list.recycle_aligned(16, 1).is_ok());
+}
+
+#[test]
+fn layouts_map_to_the_bucket_of_their_size_or_alignment() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = block(32);
+
+    list.throw_layout(ptr, Layout::from_size_align(24, 8).unwrap()).unwrap();
+    assert_eq!(list.bucket_len(32).unwrap(), 1);
+
+    // Any layout of the same bucket gets the block
+    assert_eq!(list.recycle_layout(Layout::from_size_align(20, 1).unwrap()).unwrap(), ptr);
+
+    list.throw_layout(ptr, Layout::from_size_align(1, 4).unwrap()).unwrap();
+    assert_eq!(list.bucket_len(4).unwrap(), 1);
+
+    list.throw_layout(block(1), Layout::new::<()>()).unwrap();
+    assert_eq!(list.bucket_len(1).unwrap(), 1);
+}
+
+#[test]
+fn recycle_layout_only_hands_out_aligned_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let layout = Layout::from_size_align(8, 64).unwrap();
+
+    let base = region(256);
+    let aligned = base.wrapping_add(base.align_offset(64));
+    let misaligned = aligned.wrapping_add(64 + 8 + 16);
+
+    list.throw_layout(misaligned, layout).unwrap();
+    assert!(matches!(list.recycle_layout(layout), Err(Error::BucketEmpty)));
+
+    list.throw_layout(aligned, layout).unwrap();
+    assert_eq!(list.recycle_layout(layout).unwrap(), aligned);
+    assert_eq!(list.bucket_len(64).unwrap(), 1);
+}
+
+#[test]
+fn layouts_too_big_for_a_bucket() {
+    let list = FreeList::<u8, 8>::new();
+    let layout = Layout::from_size_align(200, 8).unwrap();
+
+    assert!(matches!(
+        list.throw_layout(block(256), layout),
+        Err(Error::BucketNotAvailable)
+    ));
+    assert!(matches!(list.recycle_layout(layout), Err(Error::BucketNotAvailable)));
+}
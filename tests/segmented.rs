@@ -0,0 +1,90 @@
+mod common;
+
+use std::cell::RefCell;
+
+use common::{block, blocks, sorted};
+use freelist::SegmentedDump;
+
+const BITS: usize = usize::BITS as usize;
+
+#[test]
+fn two_words_hold_twice_the_bits() {
+    let dump = SegmentedDump::<u8, 2>::new();
+    assert_eq!(SegmentedDump::<u8, 2>::capacity(), 2 * BITS);
+
+    let ptrs = blocks(2 * BITS, 1);
+    for &ptr in &ptrs {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert!(dump.is_full());
+    assert_eq!(dump.len(), 2 * BITS);
+
+    let extra = block(1);
+    assert_eq!(dump.throw(extra), Err(extra));
+
+    let mut recycled = Vec::new();
+    while let Some(ptr) = dump.recycle() {
+        recycled.push(ptr);
+    }
+
+    assert_eq!(sorted(recycled), ptrs);
+    assert!(dump.is_empty());
+}
+
+#[test]
+fn the_second_word_is_only_used_once_the_first_is_full() {
+    let dump = SegmentedDump::<u8, 2>::new();
+
+    for ptr in blocks(BITS, 1) {
+        dump.throw(ptr).unwrap();
+    }
+    assert!(!dump.is_full());
+
+    let overflow = block(1);
+    dump.throw(overflow).unwrap();
+    assert_eq!(dump.len(), BITS + 1);
+
+    // Recycles also go through the first word first
+    for _ in 0..BITS {
+        assert_ne!(dump.recycle(), Some(overflow));
+    }
+    assert_eq!(dump.recycle(), Some(overflow));
+    assert_eq!(dump.recycle(), None);
+}
+
+#[test]
+fn clear_goes_through_every_word() {
+    let dump = SegmentedDump::<u8, 3>::default();
+    let ptrs = blocks(2 * BITS + 5, 1);
+
+    ptrs.iter().for_each(|&ptr| dump.throw(ptr).unwrap());
+
+    let freed = RefCell::new(Vec::new());
+    dump.clear(|ptr| freed.borrow_mut().push(ptr));
+
+    assert_eq!(sorted(freed.into_inner()), ptrs);
+    assert!(dump.is_empty());
+    assert_eq!(dump.len(), 0);
+}
+
+#[test]
+fn empty_and_full_with_a_single_word() {
+    let dump = SegmentedDump::<u8, 1>::new();
+    assert!(dump.is_empty());
+    assert!(!dump.is_full());
+
+    for ptr in blocks(BITS, 1) {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert!(dump.is_full());
+    assert!(!dump.is_empty());
+}
+
+#[test]
+fn default_is_an_empty_dump() {
+    let dump = SegmentedDump::<u8, 2>::default();
+    assert!(dump.is_empty());
+    assert_eq!(SegmentedDump::<u8, 2>::capacity(), 2 * BITS);
+}
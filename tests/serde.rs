@@ -0,0 +1,63 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::blocks;
+use freelist::FreeList;
+
+#[test]
+fn bucket_stats_serialize_to_counts_and_sizes() {
+    let list = FreeList::<u8, 2>::new();
+
+    for ptr in blocks(3, 2) {
+        list.throw(ptr, 2).unwrap();
+    }
+
+    let json = serde_json::to_value(list.bucket_stats()).unwrap();
+    let buckets = json.as_array().unwrap();
+
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[1]["size"], 2);
+    assert_eq!(buckets[1]["parked"], 3);
+    assert_eq!(buckets[1]["free_slots"], list.free_slots_for(2).unwrap());
+    assert_eq!(buckets[0]["parked"], 0);
+
+    let mut keys: Vec<_> = buckets[1].as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort_unstable();
+
+    #[cfg(not(feature = "stats"))]
+    assert_eq!(keys, ["free_slots", "parked", "size"]);
+
+    #[cfg(feature = "stats")]
+    assert_eq!(
+        keys,
+        ["avg_residency", "counters", "free_slots", "parked", "pressure", "size"]
+    );
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn counters_serialize_with_every_field() {
+    let list = FreeList::<u8, 2>::new();
+
+    list.throw(blocks(1, 1)[0], 1).unwrap();
+    list.recycle(1).unwrap();
+    assert!(list.recycle(1).is_err());
+
+    let json = serde_json::to_value(list.stats()).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "hits": 1,
+            "misses": 1,
+            "throws": 1,
+            "full_throws": 0,
+            "cas_retries": 0,
+        })
+    );
+
+    let stats = serde_json::to_value(list.bucket_stats()).unwrap();
+    assert_eq!(stats[0]["counters"], json);
+    assert_eq!(stats[1]["avg_residency"], serde_json::Value::Null);
+}
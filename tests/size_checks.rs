@@ -0,0 +1,57 @@
+#![cfg(feature = "size-checks")]
+
+mod common;
+
+use std::cell::Cell;
+
+use common::{adjacent_blocks, block};
+use freelist::{record_allocation_size, Error, FreeList};
+
+#[test]
+fn try_coalesce_records_the_merged_size() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = adjacent_blocks(2, 16);
+
+    for &ptr in &ptrs {
+        record_allocation_size(ptr, 16);
+        list.throw(ptr, 16).unwrap();
+    }
+
+    assert_eq!(list.try_coalesce(16, |_, _| true).unwrap(), 1);
+
+    let merged = list.recycle(32).unwrap();
+    assert_eq!(merged, ptrs[0]);
+    assert!(matches!(list.throw(merged, 16), Err(Error::SizeMismatch(addr)) if addr == merged as usize));
+    list.throw(merged, 32).unwrap();
+}
+
+#[test]
+fn recycle_by_splitting_records_both_halves() {
+    let list = FreeList::<u8, 8>::new();
+    let halves = adjacent_blocks(2, 16);
+
+    record_allocation_size(halves[0], 32);
+    list.throw(halves[0], 32).unwrap();
+
+    assert_eq!(list.recycle_by_splitting(16).unwrap(), (halves[0], 16));
+    assert!(matches!(list.throw(halves[0], 32), Err(Error::SizeMismatch(_))));
+    assert_eq!(list.recycle(16).unwrap(), halves[1]);
+    assert!(matches!(list.throw(halves[1], 32), Err(Error::SizeMismatch(_))));
+}
+
+#[test]
+fn get_or_alloc_frees_mismatched_blocks() {
+    let list = FreeList::<u8, 8>::new();
+    let (ptr, fresh) = (block(32), block(32));
+    let freed = Cell::new(None);
+
+    record_allocation_size(ptr, 32);
+    list.throw(ptr, 32).unwrap();
+
+    // Recorded again while parked, as if the address was reused
+    record_allocation_size(ptr, 16);
+
+    assert_eq!(list.get_or_alloc(32, |_| fresh, |ptr| freed.set(Some(ptr))), (fresh, 32));
+    assert_eq!(freed.get(), Some(ptr));
+    assert!(list.is_empty());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::cell::RefCell;
+
+use common::{block, blocks};
+use freelist::{Dump, Error, SizeClassFreeList};
+
+#[test]
+fn class_size_is_the_upper_end_of_the_class() {
+    let list = SizeClassFreeList::<u8, 12, 4>::new();
+
+    assert_eq!(list.class_size(1025).unwrap(), 1280);
+    assert_eq!(list.class_size(1280).unwrap(), 1280);
+    assert_eq!(list.class_size(1281).unwrap(), 1536);
+    assert_eq!(list.class_size(2048).unwrap(), 2048);
+
+    // Fewer classes than S, one per size
+    assert_eq!(list.class_size(3).unwrap(), 3);
+    assert_eq!(list.class_size(1).unwrap(), 1);
+    assert!(matches!(list.class_size(2049), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn classes_of_a_bucket_dont_mix() {
+    let list = SizeClassFreeList::<u8, 12, 4>::new();
+    let ptr = block(1280);
+
+    list.throw(ptr, 1100).unwrap();
+
+    assert!(matches!(list.recycle(1500), Err(Error::BucketEmpty)));
+    assert!(matches!(list.recycle(2048), Err(Error::BucketEmpty)));
+    assert_eq!(list.recycle(1280).unwrap(), ptr);
+    assert!(matches!(list.recycle(1100), Err(Error::BucketEmpty)));
+}
+
+#[test]
+fn full_class_hands_the_pointer_back() {
+    let list = SizeClassFreeList::<u8, 8, 2>::new();
+
+    for ptr in blocks(Dump::<u8>::capacity(), 96) {
+        list.throw(ptr, 96).unwrap();
+    }
+
+    assert!(matches!(list.throw(block(96), 90), Err(Error::BucketFull)));
+    list.throw(block(128), 128).unwrap();
+    assert!(matches!(list.throw(block(256), 256), Err(Error::BucketNotAvailable)));
+}
+
+#[test]
+fn clear_passes_the_class_size() {
+    let list = SizeClassFreeList::<u8, 12, 4>::default();
+    let cleared = RefCell::new(Vec::new());
+
+    list.throw(block(1536), 1300).unwrap();
+    list.throw(block(64), 64).unwrap();
+
+    list.clear(|_, size| cleared.borrow_mut().push(size));
+
+    let mut cleared = cleared.into_inner();
+    cleared.sort_unstable();
+    assert_eq!(cleared, vec![64, 1536]);
+    assert!(matches!(list.recycle(1300), Err(Error::BucketEmpty)));
+}
+
+#[test]
+#[should_panic(expected = "number of size classes should be power of 2")]
+fn classes_must_be_a_power_of_2() {
+    SizeClassFreeList::<u8, 4, 3>::new();
+}
+
+#[test]
+fn default_is_an_empty_freelist() {
+    let list = SizeClassFreeList::<u8, 12, 4>::default();
+    assert!(matches!(list.recycle(1280), Err(Error::BucketEmpty)));
+
+    let ptr = block(1280);
+    list.throw(ptr, 1280).unwrap();
+    assert_eq!(list.recycle(1025).unwrap(), ptr);
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "stats")]
+
+mod common;
+
+use std::{sync::Arc, thread, time::Duration};
+
+use common::blocks;
+use freelist::{Dump, Error, FreeList};
+
+#[test]
+fn pressure_is_empty_recycles_minus_full_throws() {
+    let list = FreeList::<u8, 4>::new();
+    let capacity = Dump::<u8>::capacity();
+    let ptrs = blocks(capacity + 2, 8);
+
+    // Consumers of 2 bytes are starved
+    for _ in 0..3 {
+        assert!(matches!(list.recycle(2), Err(Error::BucketEmpty)));
+    }
+
+    // Producers of 8 bytes are
+    for &ptr in &ptrs[..capacity] {
+        list.throw(ptr, 8).unwrap();
+    }
+    for &ptr in &ptrs[capacity..] {
+        assert!(matches!(list.throw(ptr, 8), Err(Error::BucketFull)));
+    }
+
+    assert_eq!(list.pressure(), vec![(1, 0), (2, 3), (4, 0), (8, -2)]);
+}
+
+#[test]
+fn pressure_counts_every_recycle_variant() {
+    let list = FreeList::<u8, 4>::new();
+    let mut local = Vec::new();
+
+    assert!(list.recycle_with_slot(4).is_err());
+    assert!(list.try_recycle_once(4).is_err());
+    assert!(list.recycle_aligned(4, 4).is_err());
+    assert_eq!(list.recycle_if_above(4, 0).unwrap(), None);
+    assert_eq!(list.recycle_refill(4, &mut local, 2, |_, _| unreachable!()).unwrap(), 0);
+    assert!(list.take_bucket(4).unwrap().is_empty());
+
+    assert_eq!(list.pressure()[2], (4, 6));
+}
+
+#[test]
+fn pressure_of_a_recycle_that_gets_something_is_unchanged() {
+    let list = FreeList::<u8, 4>::new();
+    let ptrs = blocks(2, 4);
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 4).unwrap());
+    list.recycle(4).unwrap();
+    list.take_bucket(4).unwrap();
+
+    assert_eq!(list.pressure()[2], (4, 0));
+}
+
+#[test]
+fn counters_match_a_known_mix_of_operations() {
+    let list = FreeList::<u8, 8>::new();
+    let capacity = Dump::<u8>::capacity();
+    let ptrs = blocks(capacity + 2, 8);
+
+    for &ptr in &ptrs {
+        let _ = list.throw(ptr, 8);
+    }
+    for _ in 0..3 {
+        list.recycle(8).unwrap();
+    }
+    assert!(list.recycle(16).is_err());
+
+    let stats = list.stats();
+    assert_eq!(stats.hits, 3);
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.throws, capacity);
+    assert_eq!(stats.full_throws, 2);
+
+    let buckets = list.bucket_stats();
+    assert_eq!(buckets[3].counters.hits, 3);
+    assert_eq!(buckets[3].counters.throws, capacity);
+    assert_eq!(buckets[4].counters.misses, 1);
+}
+
+#[test]
+fn counters_count_every_recycle_variant() {
+    let list = FreeList::<u8, 8>::new();
+    let ptrs = blocks(8, 16);
+    let mut local = Vec::new();
+
+    ptrs.iter().for_each(|&ptr| list.throw(ptr, 16).unwrap());
+
+    list.recycle_with_slot(16).unwrap();
+    list.try_recycle_once(16).unwrap();
+    list.recycle_aligned(16, 1).unwrap();
+    list.recycle_refill(16, &mut local, 2, |_, _| unreachable!()).unwrap();
+    assert_eq!(list.take_bucket(16).unwrap().len(), 3);
+    assert_eq!(list.stats().hits, 8);
+
+    // A miss for every bucket from 16 to 128 bytes
+    assert!(list.recycle_or_larger(16).is_err());
+
+    let stats = list.stats();
+    assert_eq!((stats.hits, stats.misses), (8, 4));
+}
+
+#[test]
+fn checkout_counts_a_hit_or_a_miss() {
+    let list = FreeList::<u8, 8>::new();
+    let ptr = blocks(1, 32)[0];
+
+    drop(list.checkout(32, |_| ptr, |_| unreachable!()));
+    drop(list.checkout(32, |_| unreachable!(), |_| unreachable!()));
+
+    let stats = list.stats();
+    assert_eq!((stats.hits, stats.misses, stats.throws), (1, 1, 2));
+}
+
+#[test]
+fn residency_is_the_time_spent_parked() {
+    let list = FreeList::<u8, 4>::new();
+
+    assert!(list.avg_residency().is_empty());
+
+    list.throw(blocks(1, 2)[0], 2).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    list.recycle(2).unwrap();
+
+    let residency = list.avg_residency();
+    assert_eq!(residency.len(), 1);
+    assert_eq!(residency[0].0, 2);
+    assert!(residency[0].1 >= Duration::from_millis(20));
+}
+
+#[test]
+fn cleared_values_dont_count_towards_residency() {
+    let dump = Dump::<u8>::new();
+
+    blocks(2, 1).into_iter().for_each(|ptr| dump.throw(ptr).unwrap());
+    dump.clear(|_| {});
+
+    assert_eq!(dump.avg_residency(), None);
+}
+
+#[test]
+fn nothing_is_retried_without_contention() {
+    let list = FreeList::<u8, 4>::new();
+
+    for _ in 0..100 {
+        blocks(3, 4).into_iter().for_each(|ptr| list.throw(ptr, 4).unwrap());
+        while list.recycle(4).is_ok() {}
+    }
+
+    assert_eq!(list.stats().cas_retries, 0);
+}
+
+#[test]
+fn retries_dont_skew_the_other_counters() {
+    const PER_THREAD: usize = 5000;
+
+    let list = Arc::new(FreeList::<u8, 4>::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let list = Arc::clone(&list);
+            let ptrs: Vec<usize> = blocks(PER_THREAD, 2).into_iter().map(|ptr| ptr as usize).collect();
+
+            thread::spawn(move || {
+                for ptr in ptrs {
+                    while list.throw(ptr as *mut u8, 2).is_err() {}
+                    let _ = list.recycle(2);
+                }
+            })
+        })
+        .collect();
+
+    handles.into_iter().for_each(|handle| handle.join().unwrap());
+
+    let stats = list.stats();
+    assert_eq!(stats.throws, 4 * PER_THREAD);
+    assert_eq!(stats.hits + list.len(), 4 * PER_THREAD);
+
+    // Whatever retries there were, they're all in the bucket that was used
+    let per_bucket: usize = list.bucket_stats().iter().map(|bucket| bucket.counters.cas_retries).sum();
+    assert_eq!(per_bucket, stats.cas_retries);
+    assert_eq!(list.bucket_stats()[1].counters.cas_retries, stats.cas_retries);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "strict-clear")]
+
+mod common;
+
+use std::{sync::Arc, thread};
+
+use common::blocks;
+use freelist::Dump;
+
+// Every clear below checks that the spots it takes were occupied,
+// so any inconsistency between the bitmaps panics the thread.
+#[test]
+fn clears_racing_throws_and_recycles_find_consistent_bitmaps() {
+    let dump = Arc::new(Dump::<u8>::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let dump = Arc::clone(&dump);
+            let ptrs: Vec<usize> = blocks(64, 1).into_iter().map(|ptr| ptr as usize).collect();
+
+            thread::spawn(move || {
+                for (j, &addr) in ptrs.iter().enumerate() {
+                    let _ = dump.throw(addr as *mut u8);
+
+                    match (i + j) % 4 {
+                        0 => dump.clear(|_| {}),
+                        1 => dump.clear_sorted(|_| {}),
+                        2 => drop(dump.recycle()),
+                        _ => drop(dump.coalesce(|_, _| true, |_, _| false)),
+                    }
+                }
+            })
+        })
+        .collect();
+
+    handles.into_iter().for_each(|handle| handle.join().unwrap());
+
+    dump.clear(|_| {});
+    assert!(dump.is_empty());
+    assert_eq!(dump.writer_bitmap_snapshot(), 0);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{block, blocks};
+use freelist::{Dump, Error, FreeList};
+
+#[test]
+fn dump_of_8_is_full_after_8_throws() {
+    let dump = Dump::<u8, 8>::new();
+
+    for ptr in blocks(8, 1) {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert!(dump.is_full());
+    assert_eq!(dump.free_slots(), 0);
+    assert_eq!(dump.len(), 8);
+
+    let extra = block(1);
+    assert_eq!(dump.throw(extra), Err(extra));
+    assert_eq!(dump.throw_priority(extra), Err(extra));
+
+    dump.recycle().unwrap();
+    assert!(!dump.is_full());
+    dump.throw(extra).unwrap();
+}
+
+#[test]
+fn reserved_slots_come_out_of_w() {
+    let dump = Dump::<u8, 8>::new();
+    dump.set_reserved_slots(2);
+
+    let ptrs = blocks(8, 1);
+
+    for &ptr in &ptrs[..6] {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert_eq!(dump.throw(ptrs[6]), Err(ptrs[6]));
+    dump.throw_priority(ptrs[6]).unwrap();
+    dump.throw_priority(ptrs[7]).unwrap();
+    assert!(dump.is_full());
+}
+
+#[test]
+fn freelist_buckets_hold_w_pointers() {
+    let list = FreeList::<u8, 4, 8>::new();
+
+    assert_eq!(FreeList::<u8, 4, 8>::TOTAL_CAPACITY, 32);
+    assert_eq!(list.free_slots_for(8).unwrap(), 8);
+
+    for ptr in blocks(8, 8) {
+        list.throw(ptr, 8).unwrap();
+    }
+
+    assert!(list.is_bucket_full(8).unwrap());
+    assert!(matches!(list.throw(block(8), 8), Err(Error::BucketFull)));
+    assert_eq!(
+        format!("{:?}", list),
+        "FreeList {bucket[1B]: 0/8, bucket[2B]: 0/8, bucket[4B]: 0/8, bucket[8B]: 8/8}"
+    );
+}
+
+#[test]
+fn w_defaults_to_usize_bits() {
+    assert_eq!(Dump::<u8>::capacity(), usize::BITS as usize);
+    assert_eq!(FreeList::<u8, 4>::TOTAL_CAPACITY, 4 * usize::BITS as usize);
+}
+
+#[test]
+#[should_panic(expected = "slots should fit in a bitmap")]
+fn w_cant_be_more_than_the_bitmap() {
+    Dump::<u8, { usize::BITS as usize + 1 }>::new();
+}
+
+#[cfg(target_has_atomic = "64")]
+#[test]
+fn u64_bitmaps_hold_64_on_any_target() {
+    let dump = Dump::<u8, 64, u64>::new();
+    assert_eq!(Dump::<u8, 64, u64>::capacity(), 64);
+
+    for ptr in blocks(64, 1) {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert!(dump.is_full());
+    assert_eq!(dump.reader_bitmap_snapshot(), u64::MAX);
+
+    let extra = block(1);
+    assert_eq!(dump.throw(extra), Err(extra));
+
+    dump.recycle().unwrap();
+    assert_eq!(dump.free_slots(), 1);
+}
+
+#[cfg(target_has_atomic = "64")]
+#[test]
+fn freelist_of_u64_bitmaps() {
+    let list = FreeList::<u8, 4, 64, u64>::new();
+
+    assert_eq!(FreeList::<u8, 4, 64, u64>::TOTAL_CAPACITY, 256);
+
+    for ptr in blocks(64, 4) {
+        list.throw(ptr, 4).unwrap();
+    }
+
+    assert!(list.is_bucket_full(4).unwrap());
+    assert_eq!(list.bucket_bitmaps(4).unwrap(), (u64::MAX, u64::MAX));
+    assert!(matches!(list.throw(block(4), 4), Err(Error::BucketFull)));
+}
+
+#[test]
+fn u8_bitmaps() {
+    let dump = Dump::<u8, 8, u8>::new();
+
+    for ptr in blocks(8, 1) {
+        dump.throw(ptr).unwrap();
+    }
+
+    assert_eq!(dump.reader_bitmap_snapshot(), u8::MAX);
+    assert!(dump.is_full());
+
+    while dump.recycle().is_some() {}
+    assert_eq!(dump.writer_bitmap_snapshot(), 0);
+}
+
+#[test]
+#[should_panic(expected = "slots should fit in a bitmap")]
+fn w_cant_be_more_than_b() {
+    Dump::<u8, 9, u8>::new();
+}